@@ -0,0 +1,138 @@
+#define_import_path bevy_water_shaders::baked_water_functions
+
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_water_shaders::water_functions Displaced, surface_frame, surface_position, displace_height_field
+
+struct WaterMaterial {
+    ambient: vec4<f32>,
+    diffuse_reflectance: vec4<f32>,
+    specular_reflectance: vec4<f32>,
+    shininess: f32,
+    fresnel_color: vec4<f32>,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: vec4<f32>,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    frame: f32,
+    frame_count: u32,
+    columns: u32,
+    rows: u32,
+    frame_resolution: f32,
+    tile_size: f32,
+    height_min: f32,
+    height_max: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> material: WaterMaterial;
+// Normal in rgb, see `bake::FlipbookMetadata`. The 8-bit height in alpha is too coarse to displace
+// by, so heights come from `height_texture`.
+@group(1) @binding(1)
+var surface_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var surface_sampler: sampler;
+@group(1) @binding(3)
+var foam_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var foam_sampler: sampler;
+// Heights from 0 to 1, unfilterable so it's filtered in `frame_height`
+@group(1) @binding(5)
+var height_texture: texture_2d<f32>;
+
+// Where a position in the surface frame lands in one frame of the atlases. The flipbook is baked
+// to tile, so it repeats every `tile_size` metres. Lookups are kept half a texel inside each frame
+// so filtering never picks up its neighbours.
+fn atlas_uv(surface_position: vec4<f32>, frame: u32) -> vec2<f32> {
+    var half_texel: f32 = 0.5 / material.frame_resolution;
+    var uv: vec2<f32> = clamp(
+        fract(surface_position.xz / material.tile_size + 0.5),
+        vec2<f32>(half_texel),
+        vec2<f32>(1.0 - half_texel)
+    );
+    var cell: vec2<f32> = vec2<f32>(f32(frame % material.columns), f32(frame / material.columns));
+    return (cell + uv) / vec2<f32>(f32(material.columns), f32(material.rows));
+}
+
+// The two frames either side of the current time, and how far it is from the first to the second.
+fn frames() -> vec3<f32> {
+    var first: u32 = u32(floor(material.frame)) % material.frame_count;
+    var second: u32 = (first + 1u) % material.frame_count;
+    return vec3<f32>(f32(first), f32(second), fract(material.frame));
+}
+
+// Height of one frame from 0 to 1, filtered bilinearly. `atlas_uv` keeps lookups half a texel
+// inside the frame, so the texels past its edges get no weight.
+fn frame_height(surface_position: vec4<f32>, frame: u32) -> f32 {
+    var size: vec2<i32> = vec2<i32>(textureDimensions(height_texture));
+    var texel: vec2<f32> = atlas_uv(surface_position, frame) * vec2<f32>(size) - 0.5;
+    var first: vec2<i32> = vec2<i32>(floor(texel));
+    var last: vec2<i32> = min(first + 1, size - 1);
+    var t: vec2<f32> = fract(texel);
+    return mix(
+        mix(
+            textureLoad(height_texture, first, 0).r,
+            textureLoad(height_texture, vec2<i32>(last.x, first.y), 0).r,
+            t.x
+        ),
+        mix(
+            textureLoad(height_texture, vec2<i32>(first.x, last.y), 0).r,
+            textureLoad(height_texture, last, 0).r,
+            t.x
+        ),
+        t.y
+    );
+}
+
+// Normal (xyz) and height (w) of the surface, blended between frames.
+fn baked_surface(surface_position: vec4<f32>) -> vec4<f32> {
+    if material.frame_count == 0u {
+        return vec4<f32>(0.0, 1.0, 0.0, 0.0);
+    }
+    var blend: vec3<f32> = frames();
+    var texel: vec4<f32> = mix(
+        textureSampleLevel(surface_texture, surface_sampler, atlas_uv(surface_position, u32(blend.x)), 0.0),
+        textureSampleLevel(surface_texture, surface_sampler, atlas_uv(surface_position, u32(blend.y)), 0.0),
+        blend.z
+    );
+    var height: f32 = mix(
+        frame_height(surface_position, u32(blend.x)),
+        frame_height(surface_position, u32(blend.y)),
+        blend.z
+    );
+    var normal: vec3<f32> = normalize(texel.xyz * 2.0 - 1.0);
+    return vec4<f32>(normal, mix(material.height_min, material.height_max, height));
+}
+
+// How much of the surface is covered in foam, from 0 to 1.
+fn baked_foam(surface_position: vec4<f32>) -> f32 {
+    if material.frame_count == 0u {
+        return 0.0;
+    }
+    var blend: vec3<f32> = frames();
+    return mix(
+        textureSampleLevel(foam_texture, foam_sampler, atlas_uv(surface_position, u32(blend.x)), 0.0).r,
+        textureSampleLevel(foam_texture, foam_sampler, atlas_uv(surface_position, u32(blend.y)), 0.0).r,
+        blend.z
+    );
+}
+
+// Slope along the surface's x and z of a baked normal.
+fn baked_slope(surface: vec4<f32>) -> vec2<f32> {
+    return -surface.xz / max(surface.y, 0.001);
+}
+
+// Displace a vertex of the mesh by the baked heights. Shared by the main and prepass shaders.
+fn displace(vertex_position: vec3<f32>) -> Displaced {
+    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex_position, 1.0));
+    var frame: mat3x3<f32> = surface_frame();
+    var surface: vec4<f32> = baked_surface(surface_position(frame, base_world_position));
+    return displace_height_field(frame, base_world_position, surface.w * material.fade_amplitude, baked_slope(surface) * material.fade_amplitude);
+}
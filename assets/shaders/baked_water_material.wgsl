@@ -0,0 +1,77 @@
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_water_shaders::water_functions Displaced, faded_out, rest_world_position, surface_frame, surface_position, height_field_normal
+#import bevy_water_shaders::water_lighting Shading, ShadingTerms, shading_terms, with_effects, combine, EFFECT_FOAM
+#import bevy_water_shaders::water_debug DEBUG_VIEW_SHADED, surface_jacobian, debug_color, with_wireframe
+#import bevy_water_shaders::baked_water_functions material, displace, baked_surface, baked_slope, baked_foam
+
+struct MeshVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) rest_position: vec4<f32>,
+    @location(4) uv: vec2<f32>,
+}
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+fn shading() -> Shading {
+    return Shading(
+        material.ambient,
+        material.diffuse_reflectance,
+        material.specular_reflectance,
+        material.shininess,
+        material.fresnel_color,
+        material.fresnel_bias,
+        material.fresnel_strength,
+        material.fresnel_shininess,
+        material.tip_attenuation,
+        material.tip_color
+    );
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> MeshVertexOutput {
+    var displaced: Displaced = displace(vertex.position);
+
+    var out: MeshVertexOutput;
+    out.world_position = displaced.world_position;
+    out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
+    out.world_normal = displaced.world_normal;
+    out.world_tangent = displaced.world_tangent;
+    out.rest_position = rest_world_position(vertex.position);
+    out.uv = vertex.uv;
+    return out;
+}
+
+@fragment
+fn fragment(
+    mesh: MeshVertexOutput,
+) -> @location(0) vec4<f32> {
+    if faded_out(mesh.position, material.fade_min, material.fade_max) {
+        discard;
+    }
+
+    var frame: mat3x3<f32> = surface_frame();
+    var position: vec4<f32> = surface_position(frame, mesh.world_position);
+    var surface: vec4<f32> = baked_surface(position);
+    var normal: vec3<f32> = height_field_normal(frame, baked_slope(surface) * material.fade_amplitude);
+
+    var terms: ShadingTerms = with_effects(shading_terms(shading(), mesh.world_position, normal, surface.w * material.fade_amplitude), material.effects);
+    var foam: f32 = 0.0;
+    if (material.effects & EFFECT_FOAM) != 0u {
+        foam = baked_foam(position) * material.fade_amplitude;
+    }
+    var color: vec4<f32> = mix(combine(terms), vec4<f32>(1.0), foam);
+    if material.debug_view != DEBUG_VIEW_SHADED {
+        var rest_position: vec4<f32> = surface_position(frame, mesh.rest_position);
+        var jacobian: f32 = surface_jacobian(rest_position.xz, position.xz);
+        // The foam mask shows the baked foam itself, even where the quality tier leaves it out
+        var foam_mask: f32 = baked_foam(position) * material.fade_amplitude;
+        color = debug_color(material.debug_view, terms, normal, position.y - rest_position.y, foam_mask, jacobian);
+    }
+    return with_wireframe(color, mesh.uv, material.wireframe_quads);
+}
@@ -0,0 +1,193 @@
+#define_import_path bevy_water_shaders::fbm_water_functions
+
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_water_shaders::water_functions Displaced, surface_frame, surface_position, displace_height_field
+
+const PI: f32 = 3.1415926538;
+// `MAX_OCTAVES` in fbm_water.rs, divided by four
+const MAX_OCTAVE_VECTORS: i32 = 16;
+
+struct WaterMaterial {
+    detail_phases: vec3<f32>,
+    ambient: vec4<f32>,
+    diffuse_reflectance: vec4<f32>,
+    specular_reflectance: vec4<f32>,
+    shininess: f32,
+    fresnel_color: vec4<f32>,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: vec4<f32>,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
+    vertex_wave_count: u32,
+    vertex_seed: f32,
+    vertex_seed_iter: f32,
+    vertex_frequency: f32,
+    vertex_frequency_mult: f32,
+    vertex_amplitude: f32,
+    vertex_amplitude_mult: f32,
+    vertex_initial_speed: f32,
+    vertex_speed_ramp: f32,
+    vertex_drag: f32,
+    vertex_height: f32,
+    vertex_max_peak: f32,
+    vertex_peak_offset: f32,
+    fragment_wave_count: u32,
+    fragment_seed: f32,
+    fragment_seed_iter: f32,
+    fragment_frequency: f32,
+    fragment_frequency_mult: f32,
+    fragment_amplitude: f32,
+    fragment_amplitude_mult: f32,
+    fragment_initial_speed: f32,
+    fragment_speed_ramp: f32,
+    fragment_drag: f32,
+    fragment_height: f32,
+    fragment_max_peak: f32,
+    fragment_peak_offset: f32,
+    lod_near: f32,
+    lod_far: f32,
+    lod_camera: vec3<f32>,
+    vertex_min_wave_count: u32,
+    vertex_peak_mean: f32,
+    fragment_min_wave_count: u32,
+    fragment_peak_mean: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    // Phase of each octave at the current time, in 0..2π, packed four to a vector
+    vertex_phases: array<vec4<f32>, MAX_OCTAVE_VECTORS>,
+    fragment_phases: array<vec4<f32>, MAX_OCTAVE_VECTORS>,
+}
+
+@group(1) @binding(0)
+var<uniform> material: WaterMaterial;
+
+fn vertex_phase(octave: u32) -> f32 {
+    return material.vertex_phases[octave / 4u][octave % 4u];
+}
+
+fn fragment_phase(octave: u32) -> f32 {
+    return material.fragment_phases[octave / 4u][octave % 4u];
+}
+
+// Fractional number of octaves to evaluate at a given distance from the camera, fading from the
+// full count at `lod_near` down to the minimum count at `lod_far`.
+fn distance_wave_count(world_position: vec4<f32>, camera_position: vec3<f32>, max_count: u32, min_count: u32) -> f32 {
+    var distance: f32 = length(camera_position - world_position.xyz);
+    var t: f32 = saturate((distance - material.lod_near) / max(material.lod_far - material.lod_near, 0.0001));
+    t = t * t * (3.0 - 2.0 * t);
+    return mix(f32(max_count), f32(min(min_count, max_count)), t);
+}
+
+// Fractional number of octaves whose wavelength is still resolved by the pixel footprint.
+fn footprint_wave_count(world_position: vec4<f32>) -> f32 {
+    var footprint: f32 = max(length(dpdx(world_position.xyz)), length(dpdy(world_position.xyz)));
+    if material.fragment_frequency_mult <= 1.0 || footprint <= 0.0 || material.fragment_frequency <= 0.0 {
+        return f32(material.fragment_wave_count);
+    }
+    // Octave i has frequency `fragment_frequency * fragment_frequency_mult^i`, and aliases once
+    // half its wavelength is smaller than a pixel.
+    return log(PI / (material.fragment_frequency * footprint)) / log(material.fragment_frequency_mult);
+}
+
+// Returns the height followed by its slope along the surface's x and z.
+fn vertex_fbm(world_position: vec4<f32>, wave_count: f32) -> vec3<f32> {
+    var frequency: f32 = material.vertex_frequency;
+    var amplitude: f32 = material.vertex_amplitude;
+    var seed: f32 = material.vertex_seed;
+    var position: vec3<f32> = world_position.xyz;
+    var amplitude_sum: f32 = 0.0;
+
+    var height: f32 = 0.0;
+    var slope: vec2<f32> = vec2<f32>(0.0);
+    for (var i: u32 = u32(0); i < material.vertex_wave_count; i++) {
+        // Octaves past the LOD cutoff are replaced by their average height, so dropping them
+        // doesn't shift the surface.
+        var weight: f32 = saturate(wave_count - f32(i));
+        var mean: f32 = amplitude * material.vertex_peak_mean;
+        if weight <= 0.0 {
+            height += mean;
+        } else {
+            var direction: vec2<f32> = normalize(vec2<f32>(cos(seed), sin(seed)));
+
+            var x = dot(direction, position.xz) * frequency + vertex_phase(i);
+            var wave = amplitude * exp(material.vertex_max_peak * sin(x) - material.vertex_peak_offset);
+
+            height += mix(mean, wave, weight);
+
+            var dx = material.vertex_max_peak * wave * cos(x) * weight;
+            slope += frequency * direction * dx;
+            position.x += direction.x * -dx * amplitude * material.vertex_drag;
+            position.z += direction.y * -dx * amplitude * material.vertex_drag;
+        }
+
+        amplitude_sum += 1.0;
+        frequency *= material.vertex_frequency_mult;
+        amplitude *= material.vertex_amplitude_mult;
+        seed += material.vertex_seed_iter;
+	}
+
+	var output: vec3<f32> = vec3<f32>(height, slope.x, slope.y) / amplitude_sum * material.vertex_height;
+	return output;
+}
+
+fn fragment_fbm(world_position: vec4<f32>, wave_count: f32) -> vec3<f32> {
+    var frequency: f32 = material.fragment_frequency;
+    var amplitude: f32 = material.fragment_amplitude;
+    var seed: f32 = material.fragment_seed;
+	var position: vec3<f32> = world_position.xyz;
+	var amplitude_sum: f32 = 0.0;
+
+	var height: f32 = 0.0;
+	var normal: vec2<f32> = vec2<f32>(0.0);
+
+	for (var i = u32(0); i < material.fragment_wave_count; i++) {
+		var weight: f32 = saturate(wave_count - f32(i));
+		var mean: f32 = amplitude * material.fragment_peak_mean;
+		if weight <= 0.0 {
+			height += mean;
+		} else {
+			var direction: vec2<f32> = normalize(vec2<f32>(cos(seed), sin(seed)));
+
+			var x: f32 = dot(direction, position.xz) * frequency + fragment_phase(i);
+			var wave: f32 = amplitude * exp(material.fragment_max_peak * sin(x) - material.fragment_peak_offset);
+			var dw: vec2<f32> = frequency * direction * (material.fragment_max_peak * wave * cos(x)) * weight;
+
+			height += mix(mean, wave, weight);
+			position.x += -dw.x * amplitude * material.fragment_drag;
+			position.z += -dw.y * amplitude * material.fragment_drag;
+
+			normal += dw;
+		}
+
+		amplitude_sum += amplitude;
+		frequency *= material.fragment_frequency_mult;
+		amplitude *= material.fragment_amplitude_mult;
+		seed += material.fragment_seed_iter;
+	}
+
+	var output: vec3<f32> = vec3<f32>(height, normal.x, normal.y) / amplitude_sum;
+	output.x *= material.fragment_height;
+
+	return output;
+}
+
+// Displace a vertex of the mesh by the vertex octaves, with the level of detail chosen by its
+// distance from the main camera, whichever view is drawing. Shared by the main and prepass shaders.
+fn displace(vertex_position: vec3<f32>) -> Displaced {
+    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex_position, 1.0));
+    var frame: mat3x3<f32> = surface_frame();
+
+    var wave_count: f32 = distance_wave_count(base_world_position, material.lod_camera, material.vertex_wave_count, material.vertex_min_wave_count);
+    var fbm: vec3<f32> = vertex_fbm(surface_position(frame, base_world_position), wave_count);
+
+    return displace_height_field(frame, base_world_position, fbm.x * material.fade_amplitude, fbm.yz * material.fade_amplitude);
+}
@@ -1,147 +1,52 @@
 #import bevy_pbr::mesh_functions as mesh_functions
 #import bevy_pbr::mesh_view_bindings as view_bindings
-#import bevy_pbr::mesh_bindings mesh
-#import bevy_pbr::pbr_functions as pbr_functions
-
-const PI: f32 = 3.1415926538;
+#import bevy_water_shaders::water_functions Displaced, faded_out, rest_world_position, NORMAL_MODE_VERTEX_WITH_DETAIL, surface_frame, surface_position, height_field_normal, detailed_normal
+#import bevy_water_shaders::water_lighting Shading, ShadingTerms, shading_terms, with_effects, combine
+#import bevy_water_shaders::water_debug DEBUG_VIEW_SHADED, surface_jacobian, jacobian_foam, debug_color, with_wireframe
+#import bevy_water_shaders::fbm_water_functions material, displace, distance_wave_count, footprint_wave_count, fragment_fbm
 
 struct MeshVertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_position: vec4<f32>,
     @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) height: f32,
+    @location(4) rest_position: vec4<f32>,
+    @location(5) uv: vec2<f32>,
 }
 
-struct WaterMaterial {
-    time: f32,
-    ambient: vec4<f32>,
-    diffuse_reflectance: vec4<f32>,
-    specular_reflectance: vec4<f32>,
-    shininess: f32,
-    fresnel_color: vec4<f32>,
-    fresnel_bias: f32,
-    fresnel_strength: f32,
-    fresnel_shininess: f32,
-    tip_attenuation: f32,
-    tip_color: vec4<f32>,
-    vertex_wave_count: u32,
-    vertex_seed: f32,
-    vertex_seed_iter: f32,
-    vertex_frequency: f32,
-    vertex_frequency_mult: f32,
-    vertex_amplitude: f32,
-    vertex_amplitude_mult: f32,
-    vertex_initial_speed: f32,
-    vertex_speed_ramp: f32,
-    vertex_drag: f32,
-    vertex_height: f32,
-    vertex_max_peak: f32,
-    vertex_peak_offset: f32,
-    fragment_wave_count: u32,
-    fragment_seed: f32,
-    fragment_seed_iter: f32,
-    fragment_frequency: f32,
-    fragment_frequency_mult: f32,
-    fragment_amplitude: f32,
-    fragment_amplitude_mult: f32,
-    fragment_initial_speed: f32,
-    fragment_speed_ramp: f32,
-    fragment_drag: f32,
-    fragment_height: f32,
-    fragment_max_peak: f32,
-    fragment_peak_offset: f32,
-}
-
-@group(1) @binding(0)
-var<uniform> material: WaterMaterial;
-
 struct Vertex {
     @location(0) position: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 };
 
-fn vertex_fbm(world_position: vec4<f32>) -> f32 {
-    var frequency: f32 = material.vertex_frequency;
-    var amplitude: f32 = material.vertex_amplitude;
-    var speed: f32 = material.vertex_initial_speed;
-    var seed: f32 = material.vertex_seed;
-    var position: vec3<f32> = world_position.xyz;
-    var amplitude_sum: f32 = 0.0;
-
-    var height: f32 = 0.0;
-    for (var i: u32 = u32(0); i < material.vertex_wave_count; i++) {
-        var direction: vec2<f32> = normalize(vec2<f32>(cos(seed), sin(seed)));
-
-        var x = dot(direction, position.xz) * frequency + material.time * speed;
-        var wave = amplitude * exp(material.vertex_max_peak * sin(x) - material.vertex_peak_offset);
-
-        height += wave;
-
-        var dx = material.vertex_max_peak * wave * cos(x);
-        position.x += direction.x * -dx * amplitude * material.vertex_drag;
-        position.z += direction.y * -dx * amplitude * material.vertex_drag;
-
-        amplitude_sum += 1.0;
-        frequency *= material.vertex_frequency_mult;
-        amplitude *= material.vertex_amplitude_mult;
-        speed *= material.vertex_speed_ramp;
-        seed += material.vertex_seed_iter;
-	}
-
-	var output: f32 = (height / amplitude_sum) * material.vertex_height;
-	return output;
-}
-
-fn fragment_fbm(world_position: vec4<f32>) -> vec3<f32> {
-    var frequency: f32 = material.fragment_frequency;
-    var amplitude: f32 = material.fragment_amplitude;
-    var speed: f32 = material.fragment_initial_speed;
-    var seed: f32 = material.fragment_seed;
-	var position: vec3<f32> = world_position.xyz;
-	var amplitude_sum: f32 = 0.0;
-
-	var height: f32 = 0.0;
-	var normal: vec2<f32> = vec2<f32>(0.0);
-
-	for (var i = u32(0); i < material.fragment_wave_count; i++) {
-	    var direction: vec2<f32> = normalize(vec2<f32>(cos(seed), sin(seed)));
-
-		var x: f32 = dot(direction, position.xz) * frequency + material.time * speed;
-		var wave: f32 = amplitude * exp(material.fragment_max_peak * sin(x) - material.fragment_peak_offset);
-		var dw: vec2<f32> = frequency * direction * (material.fragment_max_peak * wave * cos(x));
-
-		height += wave;
-		position.x += -dw.x * amplitude * material.fragment_drag;
-	    position.z += -dw.y * amplitude * material.fragment_drag;
-
-		normal += dw;
-
-		amplitude_sum += amplitude;
-		frequency *= material.fragment_frequency_mult;
-		amplitude *= material.fragment_amplitude_mult;
-		speed *= material.fragment_speed_ramp;
-		seed += material.fragment_seed_iter;
-	}
-
-	var output: vec3<f32> = vec3<f32>(height, normal.x, normal.y) / amplitude_sum;
-	output.x *= material.fragment_height;
-
-	return output;
+fn shading() -> Shading {
+    return Shading(
+        material.ambient,
+        material.diffuse_reflectance,
+        material.specular_reflectance,
+        material.shininess,
+        material.fresnel_color,
+        material.fresnel_bias,
+        material.fresnel_strength,
+        material.fresnel_shininess,
+        material.tip_attenuation,
+        material.tip_color
+    );
 }
 
 @vertex
 fn vertex(vertex: Vertex) -> MeshVertexOutput {
-    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex.position, 1.0));
-	var offset: vec3<f32> = vec3<f32>(0.0);
-	var normal: vec3<f32> = vec3<f32>(0.0);
-
-    var fbm: f32 = vertex_fbm(base_world_position);
-	offset.y = fbm;
-
-	var offset_position = vertex.position + offset;
-
-	var out: MeshVertexOutput;
-	out.world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(offset_position, 1.0));
-	out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
-    out.world_normal = vec3<f32>(0.0, 0.0, 0.0);
+    var displaced: Displaced = displace(vertex.position);
+
+    var out: MeshVertexOutput;
+    out.world_position = displaced.world_position;
+    out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
+    out.world_normal = displaced.world_normal;
+    out.world_tangent = displaced.world_tangent;
+    out.rest_position = rest_world_position(vertex.position);
+    out.uv = vertex.uv;
+    out.height = displaced.height;
     return out;
 }
 
@@ -149,51 +54,44 @@ fn vertex(vertex: Vertex) -> MeshVertexOutput {
 fn fragment(
     mesh: MeshVertexOutput,
 ) -> @location(0) vec4<f32> {
-    var sun_direction = vec3<f32>(1.0, -1.0, 0.0);
-    var sun_color = vec4<f32>(3.0, 1.9, 0.9, 1.0);
-
-    var light_direction: vec3<f32> = -normalize(sun_direction);
-    var view_direction: vec3<f32> = pbr_functions::calculate_view(mesh.world_position, false);
-    var halfway_direction: vec3<f32> = normalize(light_direction + view_direction);
+    if faded_out(mesh.position, material.fade_min, material.fade_max) {
+        discard;
+    }
 
     var normal: vec3<f32> = vec3<f32>(0.0);
     var height: f32 = 0.0;
 
-   	var fbm: vec3<f32> = fragment_fbm(mesh.world_position);
-   	height = fbm.x;
-   	normal.x = fbm.y;
-    normal.y = fbm.z;
-    normal = mesh_functions::mesh_normal_local_to_world(normalize(vec3<f32>(-normal.x, 1.0, -normal.y)));
-
-    var ndotl: f32 = saturate(dot(light_direction, normal));
-
-    var diffuse_reflectance: vec3<f32> = material.diffuse_reflectance.xyz / PI;
-    var diffuse: vec3<f32> = sun_color.rgb * ndotl * diffuse_reflectance;
-
-    // Schlick Fresnel
-    var fresnel_normal: vec3<f32> = normal;
-    // fresnel_normal.x *= material.fresnel_normal_strength;
-    // fresnel_normal.z *= material.fresnel_normal_strength;
-    // fresnel_normal = normalize(fresnel_normal);
-    var base: f32 = 1.0 - dot(view_direction, fresnel_normal);
-    var exponential: f32 = pow(base, material.fresnel_shininess);
-    var R: f32 = exponential + material.fresnel_bias * (1.0 - exponential);
-    R *= material.fresnel_strength;
-    var fresnel: vec3<f32> = material.fresnel_color.rgb * R;
-
-    var specular_reflectance: vec3<f32> = material.specular_reflectance.rgb;
-    var specular_normal: vec3<f32> = normal;
-    var specular_amount: f32 = pow(saturate(dot(specular_normal, halfway_direction)), material.shininess * 100.0) * ndotl;
-    var specular: vec3<f32> = sun_color.rgb * specular_reflectance * specular_amount;
-
-    // Schlick Fresnel but again for specular
-    base = 1.0 - saturate(dot(view_direction, halfway_direction));
-    exponential = pow(base, 5.0);
-    R = exponential + material.fresnel_bias * (1.0 - exponential);
-    specular *= R;
-
-    var tip_color: vec3<f32> = material.tip_color.rgb * pow(height, material.tip_attenuation);
-
-    var output: vec3<f32> = material.ambient.rgb + diffuse + specular + fresnel + tip_color;
-    return vec4<f32>(output, 1.0);
+    if material.normal_mode == NORMAL_MODE_VERTEX_WITH_DETAIL {
+        height = mesh.height;
+        normal = detailed_normal(
+            mesh.world_position,
+            mesh.world_normal,
+            mesh.world_tangent,
+            material.detail_phases,
+            material.detail_frequency,
+            material.detail_strength * material.fade_amplitude
+        );
+    } else {
+        // `normal_mode` is uniform, so the footprint's derivatives are still taken across the quad
+        var wave_count: f32 = min(
+            distance_wave_count(mesh.world_position, view_bindings::view.world_position, material.fragment_wave_count, material.fragment_min_wave_count),
+            footprint_wave_count(mesh.world_position)
+        );
+        var frame: mat3x3<f32> = surface_frame();
+        var fbm: vec3<f32> = fragment_fbm(surface_position(frame, mesh.world_position), wave_count);
+        height = fbm.x * material.fade_amplitude;
+        normal = height_field_normal(frame, fbm.yz * material.fade_amplitude);
+    }
+
+    var terms: ShadingTerms = with_effects(shading_terms(shading(), mesh.world_position, normal, height), material.effects);
+    var color: vec4<f32> = combine(terms);
+    if material.debug_view != DEBUG_VIEW_SHADED {
+        var frame: mat3x3<f32> = surface_frame();
+        var rest_position: vec4<f32> = surface_position(frame, mesh.rest_position);
+        var displaced_position: vec4<f32> = surface_position(frame, mesh.world_position);
+        var jacobian: f32 = surface_jacobian(rest_position.xz, displaced_position.xz);
+        var displaced_height: f32 = displaced_position.y - rest_position.y;
+        color = debug_color(material.debug_view, terms, normal, displaced_height, jacobian_foam(jacobian), jacobian);
+    }
+    return with_wireframe(color, mesh.uv, material.wireframe_quads);
 }
@@ -0,0 +1,42 @@
+#define_import_path bevy_water_shaders::fft_water_functions
+
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_water_shaders::water_functions Displaced, surface_frame, displace_height_field
+
+struct WaterMaterial {
+    detail_phases: vec3<f32>,
+    ambient: vec4<f32>,
+    diffuse_reflectance: vec4<f32>,
+    specular_reflectance: vec4<f32>,
+    shininess: f32,
+    fresnel_color: vec4<f32>,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: vec4<f32>,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> material: WaterMaterial;
+
+// Slope of the surface along its x and z, at a position in the surface frame.
+fn surface_slope(surface_position: vec4<f32>) -> vec2<f32> {
+    return vec2<f32>(0.0);
+}
+
+// Displace a vertex of the mesh. Shared by the main and prepass shaders.
+fn displace(vertex_position: vec3<f32>) -> Displaced {
+    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex_position, 1.0));
+    return displace_height_field(surface_frame(), base_world_position, 0.0, vec2<f32>(0.0));
+}
@@ -0,0 +1,86 @@
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_pbr::mesh_view_bindings as view_bindings
+#import bevy_pbr::prepass_bindings as prepass_bindings
+#import bevy_water_shaders::water_functions Displaced, faded_out
+#import bevy_water_shaders::fft_water_functions material, displace
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+#ifdef NORMAL_PREPASS
+    @location(1) world_normal: vec3<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(3) world_position: vec4<f32>,
+    @location(4) previous_world_position: vec4<f32>,
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    @location(5) clip_position_unclamped: vec4<f32>,
+#endif
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var displaced: Displaced = displace(vertex.position);
+
+    var out: VertexOutput;
+    out.clip_position = mesh_functions::mesh_position_world_to_clip(displaced.world_position);
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.clip_position;
+    out.clip_position.z = min(out.clip_position.z, 1.0);
+#endif
+#ifdef NORMAL_PREPASS
+    out.world_normal = displaced.world_normal;
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    // Only the entity's own movement is tracked, not the waves themselves.
+    var position = vec4<f32>(vertex.position, 1.0);
+    out.world_position = displaced.world_position;
+    out.previous_world_position = displaced.world_position
+        + mesh_functions::mesh_position_local_to_world(mesh.previous_model, position)
+        - mesh_functions::mesh_position_local_to_world(mesh.model, position);
+#endif
+    return out;
+}
+
+#ifdef PREPASS_FRAGMENT
+struct FragmentOutput {
+#ifdef NORMAL_PREPASS
+    @location(0) normal: vec4<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(1) motion_vector: vec2<f32>,
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    @builtin(frag_depth) frag_depth: f32,
+#endif
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    if faded_out(in.clip_position, material.fade_min, material.fade_max) {
+        discard;
+    }
+
+    var out: FragmentOutput;
+#ifdef NORMAL_PREPASS
+    out.normal = vec4<f32>(normalize(in.world_normal) * 0.5 + vec3<f32>(0.5), 1.0);
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    out.frag_depth = in.clip_position_unclamped.z;
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    var clip_position_t = view_bindings::view.unjittered_view_proj * in.world_position;
+    var clip_position = clip_position_t.xy / clip_position_t.w;
+    var previous_clip_position_t = prepass_bindings::previous_view_proj * in.previous_world_position;
+    var previous_clip_position = previous_clip_position_t.xy / previous_clip_position_t.w;
+    // Motion vectors are in NDC space, flipped to match texture coordinates.
+    out.motion_vector = (clip_position - previous_clip_position) * vec2<f32>(0.5, -0.5);
+#endif
+    return out;
+}
+#endif
@@ -0,0 +1,159 @@
+#define_import_path bevy_water_shaders::sum_water_functions
+
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_water_shaders::water_functions Displaced, surface_frame, surface_position, displace_height_field
+
+const WAVE_COUNT: i32 = 4;
+
+struct WaterMaterial {
+    detail_phases: vec3<f32>,
+    waves: array<mat3x3<f32>, WAVE_COUNT>,
+    ambient: vec4<f32>,
+    diffuse_reflectance: vec4<f32>,
+    specular_reflectance: vec4<f32>,
+    shininess: f32,
+    fresnel_color: vec4<f32>,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: vec4<f32>,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    wave_count: u32,
+}
+// Each wave mat3x3 is:
+//  [0][0]: direction.x
+//  [0][1]: direction.y
+//  [0][2]: frequency
+//  [1][0]: amplitude
+//  [1][1]: phase at the current time, in 0..2π
+//  [1][2]: steepness
+//  [2][0]: type (0 = Sine, 1 = SteepSine)
+//  remainder: unused
+
+struct WaveSpec {
+    ty: f32,
+    direction: vec2<f32>,
+    frequency: f32,
+    amplitude: f32,
+    phase: f32,
+    steepness: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> material: WaterMaterial;
+
+fn get_wave_coord(world_position: vec4<f32>, direction: vec2<f32>) -> f32 {
+    return world_position.x * direction.x + world_position.z * direction.y;
+}
+
+fn get_time(wave: WaveSpec) -> f32 {
+    return wave.phase;
+}
+
+fn sine_wave(world_position: vec4<f32>, wave: WaveSpec) -> f32 {
+	var xz: f32 = get_wave_coord(world_position, wave.direction);
+	var t: f32 = get_time(wave);
+	return wave.amplitude * sin(xz * wave.frequency + t);
+}
+
+fn steep_sine_wave(world_position: vec4<f32>, wave: WaveSpec) -> f32 {
+	var xz: f32 = get_wave_coord(world_position, wave.direction);
+	var t: f32 = get_time(wave);
+	return 2.0 * wave.amplitude * pow((sin(xz * wave.frequency + t) + 1.0) / 2.0, wave.steepness);
+}
+
+fn calculate_offset(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
+    var offset: f32 = 0.0;
+    if wave.ty == 0.0 {
+        return vec3<f32>(0.0, sine_wave(world_position, wave), 0.0);
+    } else if wave.ty == 1.0 {
+        return vec3<f32>(0.0, steep_sine_wave(world_position, wave), 0.0);
+    }
+    return vec3<f32>(0.0, offset, 0.0);
+}
+
+fn sine_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
+	var xz: f32 = get_wave_coord(world_position, wave.direction);
+	var t: f32 = get_time(wave);
+	var normal: vec2<f32> = wave.frequency * wave.amplitude * wave.direction * cos(xz * wave.frequency + t);
+	return vec3<f32>(normal.x, normal.y, 0.0);
+}
+
+fn steep_sine_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
+	var xz: f32 = get_wave_coord(world_position, wave.direction);
+	var t: f32 = get_time(wave);
+
+	// The height is 2a * s^k with s = (sin + 1) / 2 and k the steepness, so its slope is
+	// 2a * k * s^(k - 1) * cos / 2 along the wave
+	var height: f32 = pow((sin(xz * wave.frequency + t) + 1.0) / 2.0, wave.steepness - 1.0);
+	var normal: vec2<f32> = wave.direction * wave.steepness * wave.frequency * wave.amplitude * height * cos(xz * wave.frequency + t);
+
+	return vec3<f32>(normal.x, normal.y, 0.0);
+}
+
+fn calculate_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
+    var normal: vec3<f32> = vec3<f32>(0.0);
+    if wave.ty == 0.0 {
+        normal = sine_normal(world_position, wave);
+    } else if wave.ty == 1.0 {
+        normal = steep_sine_normal(world_position, wave);
+    }
+    return normal;
+}
+
+fn get_waves() -> array<WaveSpec, WAVE_COUNT> {
+    var waves = array<WaveSpec, WAVE_COUNT>();
+    for (var i = 0; i < WAVE_COUNT; i++) {
+        waves[i] = WaveSpec(
+            material.waves[i][2][0],                                     // type
+            vec2<f32>(material.waves[i][0][0], material.waves[i][0][1]), // direction
+            material.waves[i][0][2],                                     // frequency
+            material.waves[i][1][0],                                     // amplitude
+            material.waves[i][1][1],                                     // phase
+            material.waves[i][1][2]                                      // steepness
+        );
+    }
+    return waves;
+}
+
+// How many of the waves the quality tier draws.
+fn wave_count() -> i32 {
+    return min(i32(material.wave_count), WAVE_COUNT);
+}
+
+// Slope of the summed waves along the surface's x and z, at a position in the surface frame.
+fn surface_slope(surface_position: vec4<f32>) -> vec2<f32> {
+    var waves = get_waves();
+    var slope: vec2<f32> = vec2<f32>(0.0);
+    for (var i = 0; i < wave_count(); i++) {
+        slope += calculate_normal(surface_position, waves[i]).xy;
+    }
+    return slope;
+}
+
+// Displace a vertex of the mesh by the summed waves. Shared by the main and prepass shaders.
+fn displace(vertex_position: vec3<f32>) -> Displaced {
+    var waves = get_waves();
+
+    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex_position, 1.0));
+    var frame: mat3x3<f32> = surface_frame();
+    var base_surface_position: vec4<f32> = surface_position(frame, base_world_position);
+    var offset: vec3<f32> = vec3<f32>(0.0);
+    var slope: vec2<f32> = vec2<f32>(0.0);
+	for (var i: i32 = 0; i < wave_count(); i++) {
+	   offset += calculate_offset(base_surface_position, waves[i]);
+	   slope += calculate_normal(base_surface_position, waves[i]).xy;
+	}
+
+    return displace_height_field(frame, base_world_position, offset.y * material.fade_amplitude, slope * material.fade_amplitude);
+}
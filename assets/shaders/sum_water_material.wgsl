@@ -1,144 +1,49 @@
 #import bevy_pbr::mesh_functions as mesh_functions
-#import bevy_pbr::mesh_view_bindings as view_bindings
-#import bevy_pbr::mesh_bindings mesh
-#import bevy_pbr::pbr_functions as pbr_functions
-
-const PI: f32 = 3.1415926538;
-const WAVE_COUNT: i32 = 4;
+#import bevy_water_shaders::water_functions Displaced, faded_out, rest_world_position, NORMAL_MODE_VERTEX_WITH_DETAIL, surface_frame, surface_position, height_field_normal, detailed_normal
+#import bevy_water_shaders::water_lighting Shading, ShadingTerms, shading_terms, with_effects, combine
+#import bevy_water_shaders::water_debug DEBUG_VIEW_SHADED, surface_jacobian, jacobian_foam, debug_color, with_wireframe
+#import bevy_water_shaders::sum_water_functions material, displace, surface_slope
 
 struct MeshVertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_position: vec4<f32>,
     @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) rest_position: vec4<f32>,
+    @location(4) uv: vec2<f32>,
 }
 
-struct WaterMaterial {
-    time: f32,
-    waves: array<mat3x3<f32>, WAVE_COUNT>,
-    ambient: vec4<f32>,
-    diffuse_reflectance: vec4<f32>,
-    specular_reflectance: vec4<f32>,
-    shininess: f32,
-    fresnel_color: vec4<f32>,
-    fresnel_bias: f32,
-    fresnel_strength: f32,
-    fresnel_shininess: f32,
-    tip_attenuation: f32,
-    tip_color: vec4<f32>,
-}
-// Each wave mat3x3 is:
-//  [0][0]: direction.x
-//  [0][1]: direction.y
-//  [0][2]: frequency
-//  [1][0]: amplitude
-//  [1][1]: phase
-//  [1][2]: steepness
-//  [2][0]: type (0 = Sine, 1 = SteepSine)
-//  remainder: unused
-
-struct WaveSpec {
-    ty: f32,
-    direction: vec2<f32>,
-    frequency: f32,
-    amplitude: f32,
-    phase: f32,
-    steepness: f32,
-}
-
-@group(1) @binding(0)
-var<uniform> material: WaterMaterial;
-
 struct Vertex {
     @location(0) position: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 };
 
-fn get_wave_coord(world_position: vec4<f32>, direction: vec2<f32>) -> f32 {
-    return world_position.x * direction.x + world_position.z * direction.y;
-}
-
-fn get_time(wave: WaveSpec) -> f32 {
-    return material.time * wave.phase;
-}
-
-fn sine_wave(world_position: vec4<f32>, wave: WaveSpec) -> f32 {
-	var xz: f32 = get_wave_coord(world_position, wave.direction);
-	var t: f32 = get_time(wave);
-	return wave.amplitude * sin(xz * wave.frequency + t);
-}
-
-fn steep_sine_wave(world_position: vec4<f32>, wave: WaveSpec) -> f32 {
-	var xz: f32 = get_wave_coord(world_position, wave.direction);
-	var t: f32 = get_time(wave);
-	return 2.0 * wave.amplitude * pow((sin(xz * wave.frequency + t) + 1.0) / 2.0, wave.steepness);
-}
-
-fn calculate_offset(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
-    var offset: f32 = 0.0;
-    if wave.ty == 0.0 {
-        return vec3<f32>(0.0, sine_wave(world_position, wave), 0.0);
-    } else if wave.ty == 1.0 {
-        return vec3<f32>(0.0, steep_sine_wave(world_position, wave), 0.0);
-    }
-    return vec3<f32>(0.0, offset, 0.0);
-}
-
-fn sine_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
-	var xz: f32 = get_wave_coord(world_position, wave.direction);
-	var t: f32 = get_time(wave);
-	var normal: vec2<f32> = wave.frequency * wave.amplitude * wave.direction * cos(xz * wave.frequency + t);
-	return vec3<f32>(normal.x, normal.y, 0.0);
-}
-
-fn steep_sine_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
-	var xz: f32 = get_wave_coord(world_position, wave.direction);
-	var t: f32 = get_time(wave);
-
-	var height: f32 = pow((sin(xz * wave.frequency + t) + 1.0) / 2.0, max(1.0, wave.steepness - 1.0));
-	var normal: vec2<f32> = wave.direction * wave.steepness * wave.frequency * wave.amplitude * height * cos(xz * wave.frequency + t);
-
-	return vec3<f32>(normal.x, normal.y, 0.0);
-}
-
-fn calculate_normal(world_position: vec4<f32>, wave: WaveSpec) -> vec3<f32> {
-    var normal: vec3<f32> = vec3<f32>(0.0);
-    if wave.ty == 0.0 {
-        normal = sine_normal(world_position, wave);
-    } else if wave.ty == 1.0 {
-        normal = steep_sine_normal(world_position, wave);
-    }
-    return normal;
-}
-
-fn get_waves() -> array<WaveSpec, WAVE_COUNT> {
-    var waves = array<WaveSpec, WAVE_COUNT>();
-    for (var i = 0; i < WAVE_COUNT; i++) {
-        waves[i] = WaveSpec(
-            material.waves[i][2][0],                                     // type
-            vec2<f32>(material.waves[i][0][0], material.waves[i][0][1]), // direction
-            material.waves[i][0][2],                                     // frequency
-            material.waves[i][1][0],                                     // amplitude
-            material.waves[i][1][1],                                     // phase
-            material.waves[i][1][2]                                      // steepness
-        );
-    }
-    return waves;
+fn shading() -> Shading {
+    return Shading(
+        material.ambient,
+        material.diffuse_reflectance,
+        material.specular_reflectance,
+        material.shininess,
+        material.fresnel_color,
+        material.fresnel_bias,
+        material.fresnel_strength,
+        material.fresnel_shininess,
+        material.tip_attenuation,
+        material.tip_color
+    );
 }
 
 @vertex
 fn vertex(vertex: Vertex) -> MeshVertexOutput {
-    var waves = get_waves();
-
-    var base_world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex.position, 1.0));
-    var offset: vec3<f32> = vec3<f32>(0.0);
-	for (var i: i32 = 0; i < WAVE_COUNT; i++) {
-	   offset += calculate_offset(base_world_position, waves[i]);
-	}
-	var offset_position = vertex.position + offset;
+    var displaced: Displaced = displace(vertex.position);
 
     var out: MeshVertexOutput;
-	out.world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(offset_position, 1.0));
-	out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
-    out.world_normal = vec3<f32>(0.0, 0.0, 0.0);
+    out.world_position = displaced.world_position;
+    out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
+    out.world_normal = displaced.world_normal;
+    out.world_tangent = displaced.world_tangent;
+    out.rest_position = rest_world_position(vertex.position);
+    out.uv = vertex.uv;
     return out;
 }
 
@@ -146,51 +51,35 @@ fn vertex(vertex: Vertex) -> MeshVertexOutput {
 fn fragment(
     mesh: MeshVertexOutput,
 ) -> @location(0) vec4<f32> {
-    var waves = get_waves();
-
-    var sun_direction = vec3<f32>(1.0, -1.0, 0.0);
-    var sun_color = vec4<f32>(3.0, 1.9, 0.9, 1.0);
-
-    var light_direction: vec3<f32> = -normalize(sun_direction);
-    var view_direction: vec3<f32> = pbr_functions::calculate_view(mesh.world_position, false);
-    var halfway_direction: vec3<f32> = normalize(light_direction + view_direction);
+    if faded_out(mesh.position, material.fade_min, material.fade_max) {
+        discard;
+    }
 
     var normal: vec3<f32> = vec3<f32>(0.0);
     var height: f32 = 0.0;
-    for (var i = 0; i < WAVE_COUNT; i++) {
-        normal += calculate_normal(mesh.world_position, waves[i]);
+    if material.normal_mode == NORMAL_MODE_VERTEX_WITH_DETAIL {
+        normal = detailed_normal(
+            mesh.world_position,
+            mesh.world_normal,
+            mesh.world_tangent,
+            material.detail_phases,
+            material.detail_frequency,
+            material.detail_strength * material.fade_amplitude
+        );
+    } else {
+        var frame: mat3x3<f32> = surface_frame();
+        normal = height_field_normal(frame, surface_slope(surface_position(frame, mesh.world_position)) * material.fade_amplitude);
     }
-    normal = mesh_functions::mesh_normal_local_to_world(normalize(vec3<f32>(-normal.x, 1.0, -normal.y)));
-
-    var ndotl: f32 = saturate(dot(light_direction, normal));
-
-    var diffuse_reflectance: vec3<f32> = material.diffuse_reflectance.xyz / PI;
-    var diffuse: vec3<f32> = sun_color.rgb * ndotl * diffuse_reflectance;
 
-    // Schlick Fresnel
-    var fresnel_normal: vec3<f32> = normal;
-    // fresnel_normal.x *= material.fresnel_normal_strength;
-    // fresnel_normal.z *= material.fresnel_normal_strength;
-    // fresnel_normal = normalize(fresnel_normal);
-    var base: f32 = 1.0 - dot(view_direction, fresnel_normal);
-    var exponential: f32 = pow(base, material.fresnel_shininess);
-    var R: f32 = exponential + material.fresnel_bias * (1.0 - exponential);
-    R *= material.fresnel_strength;
-    var fresnel: vec3<f32> = material.fresnel_color.rgb * R;
-
-    var specular_reflectance: vec3<f32> = material.specular_reflectance.rgb;
-    var specular_normal: vec3<f32> = normal;
-    var specular_amount: f32 = pow(saturate(dot(specular_normal, halfway_direction)), material.shininess * 100.0) * ndotl;
-    var specular: vec3<f32> = sun_color.rgb * specular_reflectance * specular_amount;
-
-    // Schlick Fresnel but again for specular
-    base = 1.0 - saturate(dot(view_direction, halfway_direction));
-    exponential = pow(base, 5.0);
-    R = exponential + material.fresnel_bias * (1.0 - exponential);
-    specular *= R;
-
-    var tip_color: vec3<f32> = material.tip_color.rgb * pow(height, material.tip_attenuation);
-
-    var output: vec3<f32> = material.ambient.rgb + diffuse + specular + fresnel + tip_color;
-    return vec4<f32>(output, 1.0);
+    var terms: ShadingTerms = with_effects(shading_terms(shading(), mesh.world_position, normal, height), material.effects);
+    var color: vec4<f32> = combine(terms);
+    if material.debug_view != DEBUG_VIEW_SHADED {
+        var frame: mat3x3<f32> = surface_frame();
+        var rest_position: vec4<f32> = surface_position(frame, mesh.rest_position);
+        var displaced_position: vec4<f32> = surface_position(frame, mesh.world_position);
+        var jacobian: f32 = surface_jacobian(rest_position.xz, displaced_position.xz);
+        var displaced_height: f32 = displaced_position.y - rest_position.y;
+        color = debug_color(material.debug_view, terms, normal, displaced_height, jacobian_foam(jacobian), jacobian);
+    }
+    return with_wireframe(color, mesh.uv, material.wireframe_quads);
 }
@@ -0,0 +1,88 @@
+#define_import_path bevy_water_shaders::water_debug
+
+#import bevy_water_shaders::water_lighting ShadingTerms
+
+// `DebugView` in common.rs
+const DEBUG_VIEW_SHADED: u32 = 0u;
+const DEBUG_VIEW_NORMALS: u32 = 1u;
+const DEBUG_VIEW_HEIGHT: u32 = 2u;
+const DEBUG_VIEW_FRESNEL: u32 = 3u;
+const DEBUG_VIEW_SPECULAR_ONLY: u32 = 4u;
+const DEBUG_VIEW_DIFFUSE_ONLY: u32 = 5u;
+const DEBUG_VIEW_TIP: u32 = 6u;
+const DEBUG_VIEW_FOAM_MASK: u32 = 7u;
+const DEBUG_VIEW_JACOBIAN: u32 = 8u;
+
+// Below this Jacobian the surface is bunched up enough that foam would start to form.
+const FOAM_JACOBIAN: f32 = 0.8;
+
+// How much the displacement scales the area of the surface around a fragment, from the screen
+// space derivatives of its rest and displaced positions along the surface. 1 is unchanged, below 1
+// the surface is compressed and below 0 it has folded over itself.
+fn surface_jacobian(rest_position: vec2<f32>, displaced_position: vec2<f32>) -> f32 {
+    var rest: f32 = determinant(mat2x2<f32>(dpdx(rest_position), dpdy(rest_position)));
+    var displaced: f32 = determinant(mat2x2<f32>(dpdx(displaced_position), dpdy(displaced_position)));
+    if abs(rest) < 1e-12 {
+        return 1.0;
+    }
+    return displaced / rest;
+}
+
+// Foam coverage for methods that don't simulate foam, from 0 where the surface is relaxed to 1
+// where it folds over.
+fn jacobian_foam(jacobian: f32) -> f32 {
+    return 1.0 - smoothstep(0.0, FOAM_JACOBIAN, jacobian);
+}
+
+fn jacobian_color(jacobian: f32) -> vec3<f32> {
+    if jacobian <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    if jacobian < 1.0 {
+        return mix(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(1.0), jacobian);
+    }
+    return mix(vec3<f32>(1.0), vec3<f32>(0.0, 1.0, 0.0), saturate(jacobian - 1.0));
+}
+
+// Color of a debug view, with `height` in metres above the rest surface.
+fn debug_color(view: u32, terms: ShadingTerms, normal: vec3<f32>, height: f32, foam: f32, jacobian: f32) -> vec4<f32> {
+    var color: vec3<f32> = vec3<f32>(0.0);
+    if view == DEBUG_VIEW_NORMALS {
+        color = normal * 0.5 + 0.5;
+    } else if view == DEBUG_VIEW_HEIGHT {
+        var extreme: vec3<f32> = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), height > 0.0);
+        color = mix(vec3<f32>(1.0), extreme, saturate(abs(height)));
+    } else if view == DEBUG_VIEW_FRESNEL {
+        color = vec3<f32>(terms.fresnel_factor);
+    } else if view == DEBUG_VIEW_SPECULAR_ONLY {
+        color = terms.specular;
+    } else if view == DEBUG_VIEW_DIFFUSE_ONLY {
+        color = terms.diffuse;
+    } else if view == DEBUG_VIEW_TIP {
+        color = terms.tip;
+    } else if view == DEBUG_VIEW_FOAM_MASK {
+        color = vec3<f32>(foam);
+    } else if view == DEBUG_VIEW_JACOBIAN {
+        color = jacobian_color(jacobian);
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// Draw the edges of the tile mesh's triangles over `color`, from the mesh UVs of a plane with
+// `quads` quads along each side, or nothing if that's zero. `shape::Plane` splits each quad along
+// the diagonal from its (1, 0) to its (0, 1) corner.
+fn with_wireframe(color: vec4<f32>, uv: vec2<f32>, quads: u32) -> vec4<f32> {
+    // Derivatives have to be taken before any branch
+    var grid: vec2<f32> = uv * f32(quads);
+    var grid_width: vec2<f32> = max(fwidth(grid), vec2<f32>(1e-6));
+    if quads == 0u {
+        return color;
+    }
+    var cell: vec2<f32> = fract(grid);
+    // Distances to the nearest edge, in pixels
+    var edges: vec2<f32> = min(cell, 1.0 - cell) / grid_width;
+    var diagonal: f32 = abs(cell.x + cell.y - 1.0) / (grid_width.x + grid_width.y);
+    var distance: f32 = min(min(edges.x, edges.y), diagonal);
+    var line: f32 = 1.0 - smoothstep(0.5, 1.5, distance);
+    return vec4<f32>(mix(color.rgb, vec3<f32>(0.0), line), color.a);
+}
@@ -0,0 +1,106 @@
+#define_import_path bevy_water_shaders::water_functions
+
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+
+const DETAIL_WAVE_COUNT: i32 = 3;
+const NORMAL_MODE_PER_PIXEL: u32 = 0u;
+const NORMAL_MODE_VERTEX_WITH_DETAIL: u32 = 1u;
+
+// A vertex of the water surface after displacement, in world space.
+struct Displaced {
+    world_position: vec4<f32>,
+    world_normal: vec3<f32>,
+    world_tangent: vec4<f32>,
+    height: f32,
+}
+
+// Orthonormal world space frame of the undisplaced water surface. The columns are the entity's
+// local x, up and z directions with any scale and shear removed, so waves keep their world size
+// and are displaced along the true surface normal however the entity is transformed.
+fn surface_frame() -> mat3x3<f32> {
+    var normal: vec3<f32> = normalize(mesh_functions::mesh_normal_local_to_world(vec3<f32>(0.0, 1.0, 0.0)));
+    var tangent: vec3<f32> = (mesh.model * vec4<f32>(1.0, 0.0, 0.0, 0.0)).xyz;
+    var bitangent: vec3<f32> = normalize(cross(tangent, normal));
+    tangent = cross(normal, bitangent);
+    return mat3x3<f32>(tangent, normal, bitangent);
+}
+
+// World position of a vertex before it's displaced.
+fn rest_world_position(vertex_position: vec3<f32>) -> vec4<f32> {
+    return mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex_position, 1.0));
+}
+
+// Whether a fragment belongs to the other material while cross-fading between methods. Each
+// pixel gets a stable dither value in [0, 1) and is only drawn if it falls in [fade_min, fade_max),
+// so two materials with complementary ranges split the tile between them, and an empty range hides
+// a material whose turn in the transition hasn't come or has passed. Its displacement is scaled by
+// the fade amplitude separately, see `common::Fade`.
+fn faded_out(frag_coord: vec4<f32>, fade_min: f32, fade_max: f32) -> bool {
+    // Interleaved gradient noise
+    var dither: f32 = fract(52.9829189 * fract(dot(floor(frag_coord.xy), vec2<f32>(0.06711056, 0.00583715))));
+    return dither < fade_min || dither >= fade_max;
+}
+
+// Position in the surface frame, with x and z running along the water and y above it.
+fn surface_position(frame: mat3x3<f32>, world_position: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(transpose(frame) * world_position.xyz, 1.0);
+}
+
+// World space normal of a height field with the given slope along the surface's x and z.
+fn height_field_normal(frame: mat3x3<f32>, slope: vec2<f32>) -> vec3<f32> {
+    return normalize(frame * vec3<f32>(-slope.x, 1.0, -slope.y));
+}
+
+// World space tangent of a height field with the given slope, pointing along the surface's x. The
+// handedness follows Bevy's `sign * cross(N, T)` convention, so the bitangent points along its z.
+fn height_field_tangent(frame: mat3x3<f32>, slope: vec2<f32>) -> vec4<f32> {
+    return vec4<f32>(normalize(frame * vec3<f32>(1.0, slope.x, 0.0)), -1.0);
+}
+
+// Lift an undisplaced world position off the surface by a height with the given slope.
+fn displace_height_field(frame: mat3x3<f32>, base_world_position: vec4<f32>, height: f32, slope: vec2<f32>) -> Displaced {
+    var out: Displaced;
+    out.world_position = base_world_position + vec4<f32>(frame[1] * height, 0.0);
+    out.world_normal = height_field_normal(frame, slope);
+    out.world_tangent = height_field_tangent(frame, slope);
+    out.height = height;
+    return out;
+}
+
+// Cheap ripples layered over interpolated vertex normals, returned as the slope along the
+// surface's x and z. Each ripple's phase at the current time is worked out on the CPU, see
+// `common::detail_phases`.
+fn detail_slope(surface_position: vec4<f32>, phases: vec3<f32>, frequency: f32, strength: f32) -> vec2<f32> {
+    var slope: vec2<f32> = vec2<f32>(0.0);
+    var wave_frequency: f32 = frequency;
+    var weight: f32 = 1.0;
+    var weight_sum: f32 = 0.0;
+    for (var i = 0; i < DETAIL_WAVE_COUNT; i++) {
+        // Spread the ripple directions out by the golden angle
+        var angle: f32 = f32(i) * 2.3999632;
+        var direction: vec2<f32> = vec2<f32>(cos(angle), sin(angle));
+        var x: f32 = dot(direction, surface_position.xz) * wave_frequency + phases[i];
+        slope += direction * weight * cos(x);
+        weight_sum += weight;
+        wave_frequency *= 1.9;
+        weight *= 0.5;
+    }
+    return slope / weight_sum * strength;
+}
+
+// Tilt an interpolated vertex normal by the detail ripples, in the surface's tangent frame.
+fn detailed_normal(
+    world_position: vec4<f32>,
+    world_normal: vec3<f32>,
+    world_tangent: vec4<f32>,
+    phases: vec3<f32>,
+    frequency: f32,
+    strength: f32,
+) -> vec3<f32> {
+    var normal: vec3<f32> = normalize(world_normal);
+    var tangent: vec3<f32> = normalize(world_tangent.xyz);
+    var bitangent: vec3<f32> = world_tangent.w * cross(normal, tangent);
+    var slope: vec2<f32> = detail_slope(surface_position(surface_frame(), world_position), phases, frequency, strength);
+    return normalize(normal - slope.x * tangent - slope.y * bitangent);
+}
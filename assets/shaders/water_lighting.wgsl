@@ -0,0 +1,127 @@
+#define_import_path bevy_water_shaders::water_lighting
+
+#import bevy_pbr::mesh_view_bindings as view_bindings
+#import bevy_pbr::mesh_view_types as view_types
+#import bevy_pbr::mesh_types as mesh_types
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_pbr::pbr_functions as pbr_functions
+#import bevy_pbr::shadows as shadows
+
+const PI: f32 = 3.1415926538;
+
+// `WaterQuality` effect flags in quality.rs
+const EFFECT_REFLECTIONS: u32 = 1u;
+const EFFECT_FOAM: u32 = 2u;
+
+// The shading parameters shared by every water material.
+struct Shading {
+    ambient: vec4<f32>,
+    diffuse_reflectance: vec4<f32>,
+    specular_reflectance: vec4<f32>,
+    shininess: f32,
+    fresnel_color: vec4<f32>,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: vec4<f32>,
+}
+
+// Direction towards the sun. This follows the scene's first directional light so that shadows
+// line up with the highlights, and falls back to a fixed sun when there isn't one.
+fn direction_to_sun() -> vec3<f32> {
+    if view_bindings::lights.n_directional_lights > 0u {
+        return normalize(view_bindings::lights.directional_lights[0].direction_to_light);
+    }
+    var sun_direction = vec3<f32>(1.0, -1.0, 0.0);
+    return -normalize(sun_direction);
+}
+
+// How much of the sun reaches a point, from 0 (fully shadowed) to 1 (fully lit).
+fn sun_visibility(world_position: vec4<f32>, normal: vec3<f32>) -> f32 {
+    if view_bindings::lights.n_directional_lights == 0u
+        || (mesh.flags & mesh_types::MESH_FLAGS_SHADOW_RECEIVER_BIT) == 0u
+        || (view_bindings::lights.directional_lights[0].flags & view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) == 0u {
+        return 1.0;
+    }
+    var view_z: f32 = dot(vec4<f32>(
+        view_bindings::view.inverse_view[0].z,
+        view_bindings::view.inverse_view[1].z,
+        view_bindings::view.inverse_view[2].z,
+        view_bindings::view.inverse_view[3].z
+    ), world_position);
+    return shadows::fetch_directional_shadow(0u, world_position, normal, view_z);
+}
+
+// The contributions `shade` adds up, kept apart so the debug views can show each one.
+struct ShadingTerms {
+    ambient: vec3<f32>,
+    diffuse: vec3<f32>,
+    specular: vec3<f32>,
+    fresnel: vec3<f32>,
+    // The Fresnel term before the Fresnel color is applied
+    fresnel_factor: f32,
+    tip: vec3<f32>,
+}
+
+fn shading_terms(shading: Shading, world_position: vec4<f32>, normal: vec3<f32>, height: f32) -> ShadingTerms {
+    var sun_color = vec4<f32>(3.0, 1.9, 0.9, 1.0);
+
+    var light_direction: vec3<f32> = direction_to_sun();
+    var view_direction: vec3<f32> = pbr_functions::calculate_view(world_position, false);
+    var halfway_direction: vec3<f32> = normalize(light_direction + view_direction);
+
+    var ndotl: f32 = saturate(dot(light_direction, normal)) * sun_visibility(world_position, normal);
+
+    var diffuse_reflectance: vec3<f32> = shading.diffuse_reflectance.xyz / PI;
+    var diffuse: vec3<f32> = sun_color.rgb * ndotl * diffuse_reflectance;
+
+    // Schlick Fresnel
+    var fresnel_normal: vec3<f32> = normal;
+    // fresnel_normal.x *= material.fresnel_normal_strength;
+    // fresnel_normal.z *= material.fresnel_normal_strength;
+    // fresnel_normal = normalize(fresnel_normal);
+    var base: f32 = 1.0 - dot(view_direction, fresnel_normal);
+    var exponential: f32 = pow(base, shading.fresnel_shininess);
+    var R: f32 = exponential + shading.fresnel_bias * (1.0 - exponential);
+    R *= shading.fresnel_strength;
+    var fresnel_factor: f32 = R;
+    var fresnel: vec3<f32> = shading.fresnel_color.rgb * R;
+
+    var specular_reflectance: vec3<f32> = shading.specular_reflectance.rgb;
+    var specular_normal: vec3<f32> = normal;
+    var specular_amount: f32 = pow(saturate(dot(specular_normal, halfway_direction)), shading.shininess * 100.0) * ndotl;
+    var specular: vec3<f32> = sun_color.rgb * specular_reflectance * specular_amount;
+
+    // Schlick Fresnel but again for specular
+    base = 1.0 - saturate(dot(view_direction, halfway_direction));
+    exponential = pow(base, 5.0);
+    R = exponential + shading.fresnel_bias * (1.0 - exponential);
+    specular *= R;
+
+    var tip_color: vec3<f32> = shading.tip_color.rgb * pow(height, shading.tip_attenuation);
+
+    return ShadingTerms(shading.ambient.rgb, diffuse, specular, fresnel, fresnel_factor, tip_color);
+}
+
+// Drop the terms of any optional effects the quality tier leaves out.
+fn with_effects(terms: ShadingTerms, effects: u32) -> ShadingTerms {
+    var out: ShadingTerms = terms;
+    if (effects & EFFECT_REFLECTIONS) == 0u {
+        out.fresnel = vec3<f32>(0.0);
+        out.fresnel_factor = 0.0;
+    }
+    if (effects & EFFECT_FOAM) == 0u {
+        out.tip = vec3<f32>(0.0);
+    }
+    return out;
+}
+
+fn combine(terms: ShadingTerms) -> vec4<f32> {
+    var output: vec3<f32> = terms.ambient + terms.diffuse + terms.specular + terms.fresnel + terms.tip;
+    return vec4<f32>(output, 1.0);
+}
+
+fn shade(shading: Shading, world_position: vec4<f32>, normal: vec3<f32>, height: f32) -> vec4<f32> {
+    return combine(shading_terms(shading, world_position, normal, height));
+}
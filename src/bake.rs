@@ -0,0 +1,685 @@
+use bevy::{
+    asset::FileAssetIo, prelude::*, render::renderer::RenderDevice, tasks::AsyncComputeTaskPool,
+};
+use bevy_egui::{egui, EguiContexts};
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, Rgb32FImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    common::WaveClock,
+    method::{MethodMaterial, WaterMethod, WaveMethod},
+    preset::PresetLibrary,
+    water_time::{loops_seamlessly, WaterTime},
+    SelectedWater,
+};
+
+pub const BAKE_DIRECTORY: &str = "bakes";
+const METADATA_EXTENSION: &str = "ron";
+/// Names of the atlases a bake writes, appended to the bake's name.
+pub const HEIGHT_ATLAS: &str = "height";
+pub const NORMAL_ATLAS: &str = "normal";
+pub const FOAM_ATLAS: &str = "foam";
+/// How far from the lowest trough to the highest crest foam starts to form. It covers the surface
+/// completely at the crest.
+const FOAM_START: f32 = 0.7;
+/// How much of a frame, from each edge, is cross-faded with the surface just past the opposite
+/// edge so the flipbook tiles without seams.
+const TILE_BLEND: f32 = 0.25;
+/// The most texels along either side of an atlas. A bake holds several floats per texel while it
+/// runs, so this keeps it to a few hundred megabytes even where the GPU could take more.
+const MAX_ATLAS_SIZE: u32 = 4096;
+/// The least every device supports, for when there's no GPU to ask.
+const DEFAULT_MAX_ATLAS_SIZE: u32 = 2048;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum BakeFormat {
+    /// 16-bit heights. Flipbook normals and foam are 8-bit so the baked material can play them
+    /// back.
+    #[default]
+    Png,
+    /// 32-bit float everything, for use in other tools.
+    Exr,
+}
+
+impl BakeFormat {
+    pub const ALL: [BakeFormat; 2] = [BakeFormat::Png, BakeFormat::Exr];
+
+    /// The format a path's extension asks for.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "png" => Some(BakeFormat::Png),
+            "exr" => Some(BakeFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            BakeFormat::Png => "png",
+            BakeFormat::Exr => "exr",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BakeFormat::Png => "PNG",
+            BakeFormat::Exr => "EXR",
+        }
+    }
+}
+
+/// What to bake: a square patch of the surface, sampled over a stretch of time.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+pub struct BakeSettings {
+    pub name: String,
+    /// Metres of surface along each side of a frame.
+    pub tile_size: f32,
+    /// Texels along each side of a frame.
+    pub resolution: u32,
+    pub frame_count: u32,
+    /// Water time of the first frame, in seconds.
+    pub start_time: f64,
+    /// Water time covered by all the frames together, in seconds.
+    pub duration: f64,
+    /// Loop the waves over `duration`, so the last frame leads straight back into the first.
+    pub seamless: bool,
+    pub format: BakeFormat,
+}
+
+impl BakeSettings {
+    /// Columns and rows of frames in the atlases, as near square as they'll go.
+    fn grid(&self) -> (u32, u32) {
+        let frame_count = self.frame_count.max(1);
+        let columns = (frame_count as f32).sqrt().ceil() as u32;
+        (columns, frame_count.div_ceil(columns))
+    }
+
+    /// Texels across and down the atlases.
+    pub fn atlas_size(&self) -> (u32, u32) {
+        let (columns, rows) = self.grid();
+        let resolution = self.resolution.max(1);
+        (columns * resolution, rows * resolution)
+    }
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        BakeSettings {
+            name: String::from("flipbook"),
+            tile_size: 20.0,
+            resolution: 128,
+            frame_count: 64,
+            start_time: 0.0,
+            duration: 8.0,
+            seamless: true,
+            format: BakeFormat::default(),
+        }
+    }
+}
+
+/// Describes a baked flipbook. It's saved next to the atlases as `<name>.ron`.
+///
+/// Each atlas lays the frames out left to right, then top to bottom. The normal atlas holds the
+/// normal mapped from `-1..1` to `0..1` in RGB, with a copy of the height in alpha. Playback takes
+/// heights from the height atlas, which is 16-bit in PNG bakes where the alpha is only 8-bit.
+/// Heights are stored as `0..1` across `height_min..height_max`.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct FlipbookMetadata {
+    pub format: BakeFormat,
+    pub frame_count: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// Texels along each side of a frame.
+    pub frame_resolution: u32,
+    /// Metres of surface along each side of a frame.
+    pub tile_size: f32,
+    /// Seconds between frames.
+    pub frame_duration: f64,
+    /// Whether the last frame leads back into the first.
+    pub looping: bool,
+    /// In metres.
+    pub height_min: f32,
+    pub height_max: f32,
+}
+
+impl FlipbookMetadata {
+    /// Read the metadata of a bake from `assets/bakes`.
+    pub fn load(name: &str) -> io::Result<Self> {
+        let ron = fs::read_to_string(metadata_path(&bake_directory(), name))?;
+        ron::de::from_str(&ron).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    fn atlas_size(&self) -> (u32, u32) {
+        (
+            self.columns * self.frame_resolution,
+            self.rows * self.frame_resolution,
+        )
+    }
+
+    /// Index into an atlas of a texel of a frame.
+    fn atlas_index(&self, frame: u32, x: u32, z: u32) -> usize {
+        let (width, _) = self.atlas_size();
+        let column = frame % self.columns;
+        let row = frame / self.columns;
+        let atlas_x = column * self.frame_resolution + x;
+        let atlas_y = row * self.frame_resolution + z;
+        (atlas_y * width + atlas_x) as usize
+    }
+
+    /// The stored `0..1` value of a height.
+    fn normalized_height(&self, height: f32) -> f32 {
+        let range = self.height_max - self.height_min;
+        if range > 0.0 {
+            ((height - self.height_min) / range).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A baked animation of the surface, ready to save.
+pub struct Flipbook {
+    pub metadata: FlipbookMetadata,
+    /// Atlas texels, row by row.
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    foam: Vec<f32>,
+}
+
+pub fn bake_directory() -> PathBuf {
+    FileAssetIo::get_base_path()
+        .join("assets")
+        .join(BAKE_DIRECTORY)
+}
+
+fn metadata_path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(format!("{name}.{METADATA_EXTENSION}"))
+}
+
+fn atlas_path(directory: &Path, name: &str, atlas: &str, format: BakeFormat) -> PathBuf {
+    directory.join(format!("{name}_{atlas}.{}", format.extension()))
+}
+
+/// Path of one of a bake's atlases, for the asset server.
+pub fn atlas_asset_path(name: &str, atlas: &str, format: BakeFormat) -> String {
+    format!("{BAKE_DIRECTORY}/{name}_{atlas}.{}", format.extension())
+}
+
+/// The names of the bakes in `assets/bakes`.
+pub fn bake_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(bake_directory())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == METADATA_EXTENSION)
+                })
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Heights and normals of a material's surface at the centres of a `width` x `height` grid of
+/// texels, `texel_size` metres apart and centred on `center`, row by row along z.
+///
+/// The surface is sampled with a border of one texel, so slopes at the edges can be taken from
+/// neighbouring samples like everywhere else.
+pub fn sample_surface<M: WaterMethod>(
+    material: &M,
+    center: Vec2,
+    texel_size: Vec2,
+    width: u32,
+    height: u32,
+) -> (Vec<f32>, Vec<Vec3>) {
+    let (width, height) = (width as usize, height as usize);
+    let padded_width = width + 2;
+    let corner = center - Vec2::new(width as f32, height as f32) * texel_size / 2.0;
+    let mut samples = vec![0.0; padded_width * (height + 2)];
+    for z in 0..height + 2 {
+        for x in 0..padded_width {
+            let position = corner + (Vec2::new(x as f32, z as f32) - 0.5) * texel_size;
+            samples[z * padded_width + x] = material.height_at(position);
+        }
+    }
+
+    let mut heights = Vec::with_capacity(width * height);
+    let mut normals = Vec::with_capacity(width * height);
+    for z in 1..=height {
+        for x in 1..=width {
+            let sample = |x: usize, z: usize| samples[z * padded_width + x];
+            let slope = Vec2::new(
+                sample(x + 1, z) - sample(x - 1, z),
+                sample(x, z + 1) - sample(x, z - 1),
+            ) / (2.0 * texel_size);
+            heights.push(sample(x, z));
+            normals.push(Vec3::new(-slope.x, 1.0, -slope.y).normalize());
+        }
+    }
+    (heights, normals)
+}
+
+/// Heights and normals of a `resolution` x `resolution` frame of `tile_size` metres, centred on the
+/// origin, that repeats seamlessly every `tile_size` metres.
+///
+/// The surface is sampled past the frame's far edges, and that overhang is cross-faded into its
+/// near edges. Each edge then leads on into the opposite one, and the normals are taken across the
+/// wrap so they agree with the heights.
+fn sample_tileable_frame<M: WaterMethod>(
+    material: &M,
+    tile_size: f32,
+    resolution: u32,
+) -> (Vec<f32>, Vec<Vec3>) {
+    let resolution = resolution as usize;
+    let blend = ((resolution as f32 * TILE_BLEND).round() as usize).clamp(1, resolution);
+    let sampled = resolution + blend;
+    let texel_size = tile_size / resolution as f32;
+    let corner = Vec2::splat(-tile_size / 2.0);
+    let mut samples = vec![0.0; sampled * sampled];
+    for z in 0..sampled {
+        for x in 0..sampled {
+            let position = corner + (Vec2::new(x as f32, z as f32) + 0.5) * texel_size;
+            samples[z * sampled + x] = material.height_at(position);
+        }
+    }
+
+    // Weight of the sample itself over the one a tile further on, easing in across the blend
+    let weight = |texel: usize| {
+        let t = (texel as f32 + 0.5) / blend as f32;
+        t * t * (3.0 - 2.0 * t)
+    };
+    let mut rows = vec![0.0; sampled * resolution];
+    for z in 0..sampled {
+        for x in 0..resolution {
+            let near = samples[z * sampled + x];
+            rows[z * resolution + x] = if x < blend {
+                let far = samples[z * sampled + x + resolution];
+                far + (near - far) * weight(x)
+            } else {
+                near
+            };
+        }
+    }
+    let mut heights = vec![0.0; resolution * resolution];
+    for z in 0..resolution {
+        for x in 0..resolution {
+            let near = rows[z * resolution + x];
+            heights[z * resolution + x] = if z < blend {
+                let far = rows[(z + resolution) * resolution + x];
+                far + (near - far) * weight(z)
+            } else {
+                near
+            };
+        }
+    }
+
+    let height = |x: usize, z: usize| heights[(z % resolution) * resolution + x % resolution];
+    let mut normals = Vec::with_capacity(resolution * resolution);
+    for z in 0..resolution {
+        for x in 0..resolution {
+            let slope = Vec2::new(
+                height(x + 1, z) - height(x + resolution - 1, z),
+                height(x, z + 1) - height(x, z + resolution - 1),
+            ) / (2.0 * texel_size);
+            normals.push(Vec3::new(-slope.x, 1.0, -slope.y).normalize());
+        }
+    }
+    (heights, normals)
+}
+
+/// Sample a material's surface into a flipbook, counting up `frames_done` as it goes. Everything is
+/// worked out from the material's `height_at` on the CPU, so this runs without a window or GPU.
+pub fn bake_flipbook<M: WaterMethod>(
+    material: &M,
+    settings: &BakeSettings,
+    frames_done: &AtomicU32,
+) -> Flipbook {
+    let resolution = settings.resolution.max(1);
+    let frame_count = settings.frame_count.max(1);
+    let (columns, rows) = settings.grid();
+    let frame_duration = settings.duration / frame_count as f64;
+    let mut metadata = FlipbookMetadata {
+        format: settings.format,
+        frame_count,
+        columns,
+        rows,
+        frame_resolution: resolution,
+        tile_size: settings.tile_size,
+        frame_duration,
+        looping: settings.seamless,
+        height_min: f32::MAX,
+        height_max: f32::MIN,
+    };
+    let (width, height) = metadata.atlas_size();
+    let texel_count = (width * height) as usize;
+    let mut flipbook = Flipbook {
+        heights: vec![0.0; texel_count],
+        normals: vec![Vec3::Y; texel_count],
+        foam: vec![0.0; texel_count],
+        metadata: metadata.clone(),
+    };
+
+    let mut material = material.clone();
+    for frame in 0..frame_count {
+        material.set_clock(WaveClock {
+            time: settings.start_time + frame as f64 * frame_duration,
+            loop_period: settings.seamless.then_some(settings.duration),
+        });
+        let (heights, normals) = sample_tileable_frame(&material, settings.tile_size, resolution);
+        for z in 0..resolution {
+            for x in 0..resolution {
+                let sample = (z * resolution + x) as usize;
+                let index = metadata.atlas_index(frame, x, z);
+                flipbook.heights[index] = heights[sample];
+                flipbook.normals[index] = normals[sample];
+                metadata.height_min = metadata.height_min.min(heights[sample]);
+                metadata.height_max = metadata.height_max.max(heights[sample]);
+            }
+        }
+        frames_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Crests get foam, by how close they come to the highest one
+    for (foam, &height) in flipbook.foam.iter_mut().zip(&flipbook.heights) {
+        let t = ((metadata.normalized_height(height) - FOAM_START) / (1.0 - FOAM_START))
+            .clamp(0.0, 1.0);
+        *foam = t * t * (3.0 - 2.0 * t);
+    }
+    flipbook.metadata = metadata;
+    flipbook
+}
+
+impl Flipbook {
+    /// Write the atlases and metadata to `directory`, as `<name>_height`, `<name>_normal`,
+    /// `<name>_foam` and `<name>.ron`.
+    pub fn save(&self, directory: &Path, name: &str) -> io::Result<()> {
+        let metadata = &self.metadata;
+        let (width, height) = metadata.atlas_size();
+        let heights: Vec<f32> = self
+            .heights
+            .iter()
+            .map(|&height| metadata.normalized_height(height))
+            .collect();
+        let encoded_normal = |index: usize| {
+            let normal = self.normals[index] * 0.5 + 0.5;
+            [normal.x, normal.y, normal.z, heights[index]]
+        };
+
+        let (height_atlas, normal_atlas, foam_atlas) = match metadata.format {
+            BakeFormat::Png => {
+                let to_u8 = |value: f32| (value * 255.0).round() as u8;
+                (
+                    DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+                        let index = (y * width + x) as usize;
+                        Luma([(heights[index] * 65535.0).round() as u16])
+                    })),
+                    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+                        Rgba(encoded_normal((y * width + x) as usize).map(to_u8))
+                    })),
+                    DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                        Luma([to_u8(self.foam[(y * width + x) as usize])])
+                    })),
+                )
+            }
+            // The EXR encoder only takes RGB(A), so single channels are repeated across RGB
+            BakeFormat::Exr => (
+                DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(width, height, |x, y| {
+                    Rgb([heights[(y * width + x) as usize]; 3])
+                })),
+                DynamicImage::ImageRgba32F(ImageBuffer::from_fn(width, height, |x, y| {
+                    Rgba(encoded_normal((y * width + x) as usize))
+                })),
+                DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(width, height, |x, y| {
+                    Rgb([self.foam[(y * width + x) as usize]; 3])
+                })),
+            ),
+        };
+
+        fs::create_dir_all(directory)?;
+        for (atlas, image) in [
+            (HEIGHT_ATLAS, height_atlas),
+            (NORMAL_ATLAS, normal_atlas),
+            (FOAM_ATLAS, foam_atlas),
+        ] {
+            save_image(&image, &atlas_path(directory, name, atlas, metadata.format))?;
+        }
+        save_ron(metadata, &metadata_path(directory, name))
+    }
+}
+
+pub fn save_image(image: &DynamicImage, path: &Path) -> io::Result<()> {
+    image.save(path).map_err(io::Error::other)
+}
+
+pub fn save_ron<T: Serialize>(value: &T, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    fs::write(path, ron)
+}
+
+/// The state of the bake window, and a bake it has asked for.
+#[derive(Resource, Debug, Default)]
+pub struct Baker {
+    pub settings: BakeSettings,
+    requested: bool,
+    /// The bake running in the background, if there is one.
+    running: Option<RunningBake>,
+    /// Outcome of the last bake, shown in the window.
+    pub status: Option<String>,
+}
+
+/// Progress of a bake on the `AsyncComputeTaskPool`, shared with the task.
+#[derive(Debug)]
+struct RunningBake {
+    frame_count: u32,
+    frames_done: Arc<AtomicU32>,
+    /// Set by the task once the bake has been saved, or failed to be.
+    outcome: Arc<Mutex<Option<String>>>,
+}
+
+pub struct BakePlugin;
+
+impl Plugin for BakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Baker>()
+            .register_type::<FlipbookMetadata>()
+            .add_systems(Update, (finish_bake, bake_ui).chain());
+    }
+}
+
+/// Pick up the outcome of a bake that's finished.
+fn finish_bake(mut baker: ResMut<Baker>) {
+    let Some(outcome) = baker
+        .running
+        .as_ref()
+        .and_then(|running| running.outcome.lock().ok()?.take())
+    else {
+        return;
+    };
+    baker.running = None;
+    baker.status = Some(outcome);
+}
+
+fn bake_ui(
+    mut contexts: EguiContexts,
+    mut baker: ResMut<Baker>,
+    water_time: Res<WaterTime>,
+    render_device: Option<Res<RenderDevice>>,
+) {
+    let max_atlas_size = render_device
+        .map_or(DEFAULT_MAX_ATLAS_SIZE, |device| {
+            device.limits().max_texture_dimension_2d
+        })
+        .min(MAX_ATLAS_SIZE);
+    let baker = baker.as_mut();
+    let settings = &mut baker.settings;
+    egui::Window::new("Bake")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("bake")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut settings.name);
+                    ui.end_row();
+
+                    ui.label("Tile Size");
+                    ui.add(egui::Slider::new(&mut settings.tile_size, 1.0..=200.0).suffix(" m"));
+                    ui.end_row();
+
+                    ui.label("Resolution");
+                    ui.add(egui::Slider::new(&mut settings.resolution, 16..=1024).suffix(" px"));
+                    ui.end_row();
+
+                    ui.label("Frames");
+                    ui.add(egui::Slider::new(&mut settings.frame_count, 1..=256));
+                    ui.end_row();
+
+                    ui.label("Start Time");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut settings.start_time)
+                                .speed(0.01)
+                                .suffix(" s"),
+                        );
+                        if ui.button("Now").clicked() {
+                            settings.start_time = water_time.elapsed();
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Duration");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.duration)
+                            .speed(0.1)
+                            .clamp_range(0.1..=600.0)
+                            .suffix(" s"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Seamless Loop");
+                    ui.checkbox(&mut settings.seamless, "");
+                    ui.end_row();
+
+                    ui.label("Format");
+                    ui.horizontal(|ui| {
+                        for format in BakeFormat::ALL {
+                            ui.radio_value(&mut settings.format, format, format.label());
+                        }
+                    });
+                    ui.end_row();
+                });
+
+            let (width, height) = settings.atlas_size();
+            let fits = width <= max_atlas_size && height <= max_atlas_size;
+            if !fits {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!(
+                        "The atlases would be {width} x {height} px, over the limit of \
+                         {max_atlas_size} px. Use fewer frames or a lower resolution."
+                    ),
+                );
+            }
+
+            let valid_name = PresetLibrary::is_valid_name(&settings.name);
+            let idle = baker.running.is_none() && !baker.requested;
+            if ui
+                .add_enabled(
+                    valid_name && fits && idle,
+                    egui::Button::new("Bake Selected Water"),
+                )
+                .clicked()
+            {
+                baker.requested = true;
+                baker.status = None;
+            }
+            if let Some(running) = &baker.running {
+                let frames_done = running.frames_done.load(Ordering::Relaxed);
+                ui.add(
+                    egui::ProgressBar::new(frames_done as f32 / running.frame_count as f32)
+                        .text(format!("Frame {frames_done} of {}", running.frame_count)),
+                );
+            } else if let Some(status) = &baker.status {
+                ui.label(status);
+            }
+        });
+}
+
+/// Bake a flipbook of `material` into the bake directory, and say how it went.
+pub fn bake_and_save<M: WaterMethod>(
+    material: &M,
+    settings: &BakeSettings,
+    frames_done: &AtomicU32,
+) -> Result<String, String> {
+    let flipbook = bake_flipbook(material, settings, frames_done);
+    flipbook
+        .save(&bake_directory(), &settings.name)
+        .map_err(|error| format!("Baking failed: {error}"))?;
+    if settings.seamless && !loops_seamlessly(material, Some(settings.duration), settings.duration)
+    {
+        Ok(format!(
+            "Baked {}, but it jumps where it loops",
+            settings.name
+        ))
+    } else {
+        Ok(format!("Baked {}", settings.name))
+    }
+}
+
+/// Start baking the selected water body in the background, if the bake window asked for it and the
+/// body uses this method.
+pub fn bake_selected_water<M: WaterMethod>(
+    mut baker: ResMut<Baker>,
+    selected: Res<SelectedWater>,
+    bodies: Query<(&WaveMethod, &MethodMaterial<M>)>,
+    materials: Res<Assets<M>>,
+) {
+    if !baker.requested {
+        return;
+    }
+    let Some((wave_method, material)) = selected.0.and_then(|entity| bodies.get(entity).ok())
+    else {
+        return;
+    };
+    let (true, Some(material)) = (wave_method.is::<M>(), materials.get(&material.0)) else {
+        return;
+    };
+    baker.requested = false;
+    if !M::CPU_HEIGHT {
+        baker.status = Some(format!("{} has no CPU heights to bake", M::LABEL));
+        return;
+    }
+    let running = RunningBake {
+        frame_count: baker.settings.frame_count.max(1),
+        frames_done: Arc::default(),
+        outcome: Arc::default(),
+    };
+    let settings = baker.settings.clone();
+    let material = material.clone();
+    let frames_done = running.frames_done.clone();
+    let outcome = running.outcome.clone();
+    AsyncComputeTaskPool::get()
+        .spawn(async move {
+            let status =
+                bake_and_save(&material, &settings, &frames_done).unwrap_or_else(|error| error);
+            if let Ok(mut outcome) = outcome.lock() {
+                *outcome = Some(status);
+            }
+        })
+        .detach();
+    baker.running = Some(running);
+}
@@ -0,0 +1,366 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{
+            AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType, TextureFormat,
+        },
+    },
+};
+use bevy_egui::egui;
+use bevy_turborand::prelude::*;
+
+use crate::{
+    bake::{self, BakeFormat, FlipbookMetadata},
+    common,
+    method::WaterMethod,
+    quality::QualitySettings,
+    weather::Weather,
+    UiState,
+};
+
+/// Plays back a flipbook baked from another method, for hardware that can't afford to simulate
+/// the waves live.
+///
+/// The waves and weather are baked in, so only the shading can be changed. The baked heights only
+/// live on the GPU, so gameplay queries see a flat surface.
+#[derive(AsBindGroup, TypeUuid, Reflect, Debug, Clone, PartialEq)]
+#[uniform(0, WaterMaterialUniform)]
+#[uuid = "6f0c2d3b-95a4-4e1f-8d7c-2b3e4a5f6c71"]
+pub struct BakedWaterMaterial {
+    pub clock: common::WaveClock,
+    pub shading: common::Shading,
+    pub fade: common::Fade,
+    pub debug_draw: common::DebugDraw,
+    pub quality: QualitySettings,
+    /// Name of the bake to play, from `assets/bakes`.
+    pub flipbook: Option<String>,
+    /// Metadata of the bake that's loaded.
+    pub metadata: Option<FlipbookMetadata>,
+    /// The `flipbook` that `metadata` and the textures were loaded for.
+    loaded: Option<String>,
+    #[texture(1, visibility(vertex, fragment))]
+    #[sampler(2, visibility(vertex, fragment))]
+    pub surface_texture: Option<Handle<Image>>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub foam_texture: Option<Handle<Image>>,
+    /// The 16-bit height atlas, once it's been converted to floats. Those can't be filtered on
+    /// every device, so the shader filters them itself.
+    #[texture(5, visibility(vertex, fragment), filterable = false)]
+    pub height_texture: Option<Handle<Image>>,
+    /// The height atlas while it loads, before it's converted.
+    loading_height_texture: Option<Handle<Image>>,
+}
+
+impl BakedWaterMaterial {
+    /// Load the textures and metadata of `flipbook`, or unload them if there isn't one.
+    fn load_flipbook(&mut self, asset_server: &AssetServer) {
+        self.loaded = self.flipbook.clone();
+        self.metadata = None;
+        self.surface_texture = None;
+        self.foam_texture = None;
+        self.height_texture = None;
+        self.loading_height_texture = None;
+        let Some(name) = &self.flipbook else {
+            return;
+        };
+        match FlipbookMetadata::load(name) {
+            Ok(metadata) if metadata.format == BakeFormat::Png => {
+                self.surface_texture = Some(asset_server.load(bake::atlas_asset_path(
+                    name,
+                    bake::NORMAL_ATLAS,
+                    metadata.format,
+                )));
+                self.foam_texture = Some(asset_server.load(bake::atlas_asset_path(
+                    name,
+                    bake::FOAM_ATLAS,
+                    metadata.format,
+                )));
+                self.loading_height_texture = Some(asset_server.load(bake::atlas_asset_path(
+                    name,
+                    bake::HEIGHT_ATLAS,
+                    metadata.format,
+                )));
+                self.metadata = Some(metadata);
+            }
+            Ok(_) => warn!("Flipbook {name} can't be played back, only PNG bakes can"),
+            Err(error) => warn!("Couldn't load flipbook {name}: {error}"),
+        }
+    }
+
+    /// Position in the flipbook at the current time, in frames.
+    fn frame(&self, metadata: &FlipbookMetadata) -> f32 {
+        // A flipbook that doesn't loop jumps back from its last frame rather than blending into
+        // its first
+        let span = if metadata.looping {
+            metadata.frame_count
+        } else {
+            metadata.frame_count - 1
+        };
+        (self.clock.time / metadata.frame_duration).rem_euclid(span.max(1) as f64) as f32
+    }
+}
+
+impl WaterMethod for BakedWaterMaterial {
+    const NAME: &'static str = "Baked";
+    const LABEL: &'static str = "Baked Flipbook";
+    const CPU_HEIGHT: bool = false;
+
+    fn new_material(_rng: &mut GlobalRng) -> Self {
+        BakedWaterMaterial {
+            clock: common::WaveClock::default(),
+            shading: common::Shading::default(),
+            fade: common::Fade::default(),
+            debug_draw: common::DebugDraw::default(),
+            quality: QualitySettings::default(),
+            flipbook: None,
+            metadata: None,
+            loaded: None,
+            surface_texture: None,
+            foam_texture: None,
+            height_texture: None,
+            loading_height_texture: None,
+        }
+    }
+
+    fn set_clock(&mut self, clock: common::WaveClock) {
+        self.clock = clock;
+    }
+
+    fn apply_shading(&mut self, shading: &common::Shading) {
+        self.shading = shading.clone();
+    }
+
+    fn apply_weather(&mut self, _weather: &Weather) {}
+
+    fn set_fade(&mut self, fade: common::Fade) {
+        self.fade = fade;
+    }
+
+    fn set_debug_draw(&mut self, debug_draw: common::DebugDraw) {
+        self.debug_draw = debug_draw;
+    }
+
+    fn set_quality(&mut self, quality: QualitySettings) {
+        self.quality = quality;
+    }
+
+    fn displacement_bounds(&self) -> (Vec3, Vec3) {
+        self.metadata
+            .as_ref()
+            .map_or((Vec3::ZERO, Vec3::ZERO), |metadata| {
+                (
+                    Vec3::new(0.0, metadata.height_min, 0.0),
+                    Vec3::new(0.0, metadata.height_max, 0.0),
+                )
+            })
+    }
+
+    fn wave_counts(&self) -> common::WaveCounts {
+        // The waves are all in the flipbook
+        common::WaveCounts::default()
+    }
+
+    fn height_at(&self, _surface_position: Vec2) -> f32 {
+        0.0
+    }
+
+    fn has_content(&self) -> bool {
+        self.metadata.is_some()
+    }
+
+    fn draw_ui(&mut self, ui: &mut egui::Ui, _ui_state: &mut UiState, _rng: &mut GlobalRng) {
+        egui::Grid::new("baked")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Flipbook");
+                // The bakes on disk are only listed while the list is open
+                egui::ComboBox::from_id_source("flipbook")
+                    .selected_text(self.flipbook.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.flipbook, None, "None");
+                        for name in bake::bake_names() {
+                            ui.selectable_value(&mut self.flipbook, Some(name.clone()), name);
+                        }
+                    });
+                ui.end_row();
+
+                if let Some(metadata) = &self.metadata {
+                    ui.label("Frames");
+                    ui.label(format!(
+                        "{} at {:.0} fps",
+                        metadata.frame_count,
+                        1.0 / metadata.frame_duration
+                    ));
+                    ui.end_row();
+
+                    ui.label("Tile Size");
+                    ui.label(format!("{} m", metadata.tile_size));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn build(app: &mut App) {
+        app.add_systems(Update, load_flipbooks);
+    }
+}
+
+/// Load newly chosen flipbooks, keep their normal atlases in linear space and convert their height
+/// atlases to floats.
+fn load_flipbooks(
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BakedWaterMaterial>>,
+) {
+    let stale: Vec<_> = materials
+        .iter()
+        .filter(|(_, material)| material.flipbook != material.loaded)
+        .map(|(id, _)| id)
+        .collect();
+    for id in stale {
+        if let Some(material) = materials.get_mut(&Handle::weak(id)) {
+            material.load_flipbook(&asset_server);
+        }
+    }
+
+    // Height atlases are only bound once they've been converted
+    let loading: Vec<_> = materials
+        .iter()
+        .filter_map(|(id, material)| Some((id, material.loading_height_texture.clone()?)))
+        .collect();
+    for (id, handle) in loading {
+        let Some(image) = images.get_mut(&handle) else {
+            continue;
+        };
+        heights_to_float(image);
+        if let Some(material) = materials.get_mut(&Handle::weak(id)) {
+            material.height_texture = material.loading_height_texture.take();
+        }
+    }
+
+    // RGBA PNGs load as sRGB colour, but the normal atlas holds vectors and heights
+    for event in events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+        let is_surface_texture = materials
+            .iter()
+            .any(|(_, material)| material.surface_texture.as_ref() == Some(handle));
+        let is_height_texture = materials
+            .iter()
+            .any(|(_, material)| material.height_texture.as_ref() == Some(handle));
+        let Some(image) = images.get_mut(handle) else {
+            continue;
+        };
+        if is_surface_texture && image.texture_descriptor.format == TextureFormat::Rgba8UnormSrgb {
+            image.texture_descriptor.format = TextureFormat::Rgba8Unorm;
+        }
+        if is_height_texture {
+            heights_to_float(image);
+        }
+    }
+}
+
+/// 16-bit greyscale PNGs load as integers, which shaders can't read as heights from 0 to 1.
+fn heights_to_float(image: &mut Image) {
+    if image.texture_descriptor.format != TextureFormat::R16Uint {
+        return;
+    }
+    image.data = image
+        .data
+        .chunks_exact(2)
+        .flat_map(|texel| {
+            (u16::from_ne_bytes([texel[0], texel[1]]) as f32 / u16::MAX as f32).to_ne_bytes()
+        })
+        .collect();
+    image.texture_descriptor.format = TextureFormat::R32Float;
+}
+
+#[derive(Debug, Clone, Default, ShaderType)]
+struct WaterMaterialUniform {
+    ambient: Color,
+    diffuse_reflectance: Color,
+    specular_reflectance: Color,
+    shininess: f32,
+    fresnel_color: Color,
+    fresnel_bias: f32,
+    fresnel_strength: f32,
+    fresnel_shininess: f32,
+    tip_attenuation: f32,
+    tip_color: Color,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    frame: f32,
+    frame_count: u32,
+    columns: u32,
+    rows: u32,
+    frame_resolution: f32,
+    tile_size: f32,
+    height_min: f32,
+    height_max: f32,
+}
+
+impl AsBindGroupShaderType<WaterMaterialUniform> for BakedWaterMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> WaterMaterialUniform {
+        let mut uniform = WaterMaterialUniform {
+            ambient: self.shading.ambient,
+            diffuse_reflectance: self.shading.diffuse_reflectance,
+            specular_reflectance: self.shading.specular_reflectance,
+            shininess: self.shading.shininess,
+            fresnel_color: self.shading.fresnel.color,
+            fresnel_bias: self.shading.fresnel.bias,
+            fresnel_strength: self.shading.fresnel.strength,
+            fresnel_shininess: self.shading.fresnel.shininess,
+            tip_attenuation: self.shading.tip_attenuation,
+            tip_color: self.shading.tip_color,
+            fade_min: self.fade.min,
+            fade_max: self.fade.max,
+            fade_amplitude: self.fade.amplitude,
+            debug_view: self.debug_draw.view as u32,
+            wireframe_quads: self.debug_draw.wireframe_quads,
+            effects: self.quality.effects(),
+            ..default()
+        };
+        // Until a flipbook and its heights are loaded, `frame_count` stays zero and the shader
+        // draws a flat surface
+        if let (Some(metadata), Some(_)) = (&self.metadata, &self.height_texture) {
+            uniform.frame = self.frame(metadata);
+            uniform.frame_count = metadata.frame_count;
+            uniform.columns = metadata.columns;
+            uniform.rows = metadata.rows;
+            uniform.frame_resolution = metadata.frame_resolution as f32;
+            uniform.tile_size = metadata.tile_size;
+            uniform.height_min = metadata.height_min;
+            uniform.height_max = metadata.height_max;
+        }
+        uniform
+    }
+}
+
+impl Material for BakedWaterMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/baked_water_material.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/baked_water_material.wgsl".into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/baked_water_prepass.wgsl".into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/baked_water_prepass.wgsl".into()
+    }
+}
@@ -0,0 +1,325 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{load_selected_water, ui_state_update, UiState, PLANE_LENGTH};
+
+/// Steepest the camera can look up or down, just short of straight so yaw stays meaningful.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f32 = 0.5;
+const MAX_DISTANCE: f32 = 2000.0;
+/// How much one line of scrolling zooms the orbit camera or speeds up the fly camera.
+const SCROLL_FACTOR: f32 = 1.1;
+/// How many times faster the fly camera moves with shift held.
+const FAST_FACTOR: f32 = 4.0;
+const TOGGLE_MODE_KEY: KeyCode = KeyCode::C;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum CameraMode {
+    /// Drag with the left mouse button to circle the focus, with the right or middle button to
+    /// move it, and scroll to zoom.
+    #[default]
+    Orbit,
+    /// Hold the right mouse button to look around, move with WASD, go down and up with Q and E,
+    /// hold shift to go faster, and scroll to change speed.
+    Fly,
+}
+
+impl CameraMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "Orbit",
+            CameraMode::Fly => "Fly",
+        }
+    }
+}
+
+/// Moves a camera with the mouse and keyboard, unless egui is using them.
+///
+/// Both modes share the same state: the camera sits `distance` from `focus`, looking at it. Flying
+/// carries the focus along with the camera, so switching back to orbiting circles whatever is in
+/// front of it.
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub focus: Vec3,
+    /// Radians around the up axis, with zero looking along -z.
+    pub yaw: f32,
+    /// Radians up from the horizon, negative looking down.
+    pub pitch: f32,
+    pub distance: f32,
+    /// Metres per second.
+    pub fly_speed: f32,
+    /// Radians per pixel of mouse movement.
+    pub look_sensitivity: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController::overlooking(PLANE_LENGTH)
+    }
+}
+
+impl CameraController {
+    /// The starting view, from just above the middle of one edge of water `plane_length` across,
+    /// looking at its centre.
+    pub fn overlooking(plane_length: f32) -> Self {
+        CameraController::looking_at(Vec3::new(plane_length * 0.5, 3.0, 0.0), Vec3::ZERO)
+    }
+
+    pub fn looking_at(position: Vec3, focus: Vec3) -> Self {
+        let mut controller = CameraController {
+            mode: CameraMode::default(),
+            focus,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 1.0,
+            fly_speed: 10.0,
+            look_sensitivity: 0.005,
+        };
+        controller.set_view(position, focus);
+        controller
+    }
+
+    /// Put the camera at `position`, looking at `focus`.
+    pub fn set_view(&mut self, position: Vec3, focus: Vec3) {
+        let offset = position - focus;
+        self.focus = focus;
+        self.distance = offset.length().clamp(MIN_DISTANCE, MAX_DISTANCE);
+        let direction = offset.normalize_or_zero();
+        self.yaw = direction.x.atan2(direction.z);
+        self.pitch = -direction.y.clamp(-1.0, 1.0).asin();
+        self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.focus + self.rotation() * Vec3::Z * self.distance
+    }
+
+    fn look(&mut self, delta: Vec2) {
+        self.yaw -= delta.x * self.look_sensitivity;
+        self.pitch = (self.pitch - delta.y * self.look_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Turn the camera where it stands, moving the focus around it.
+    fn look_around(&mut self, delta: Vec2) {
+        let position = self.position();
+        self.look(delta);
+        self.focus = position - self.rotation() * Vec3::Z * self.distance;
+    }
+}
+
+/// A saved camera view, kept with the water preset.
+#[derive(Debug, Clone, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: Vec3,
+    pub focus: Vec3,
+}
+
+/// The state of the camera window.
+#[derive(Resource, Debug, Default)]
+struct CameraUi {
+    bookmark_name: String,
+}
+
+pub struct CameraControllerPlugin;
+
+impl Plugin for CameraControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraUi>()
+            .register_type::<CameraController>()
+            .register_type::<CameraBookmark>()
+            .add_systems(
+                Update,
+                (
+                    camera_ui.after(load_selected_water).before(ui_state_update),
+                    control_camera.after(camera_ui),
+                ),
+            );
+    }
+}
+
+fn control_camera(
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    mut cameras: Query<(&mut CameraController, &mut Transform)>,
+) {
+    // Events are read either way, so nothing egui used is replayed once it lets go
+    let mouse_delta: Vec2 = motion.iter().map(|event| event.delta).sum();
+    let scroll: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 100.0,
+        })
+        .sum();
+    let ctx = contexts.ctx_mut();
+    let pointer_free = !ctx.wants_pointer_input() && !ctx.is_pointer_over_area();
+    let keyboard_free = !ctx.wants_keyboard_input();
+    let (mouse_delta, scroll) = if pointer_free {
+        (mouse_delta, scroll)
+    } else {
+        (Vec2::ZERO, 0.0)
+    };
+
+    for (mut controller, mut transform) in cameras.iter_mut() {
+        if keyboard_free && keys.just_pressed(TOGGLE_MODE_KEY) {
+            controller.mode = match controller.mode {
+                CameraMode::Orbit => CameraMode::Fly,
+                CameraMode::Fly => CameraMode::Orbit,
+            };
+        }
+        match controller.mode {
+            CameraMode::Orbit => {
+                if buttons.pressed(MouseButton::Left) {
+                    controller.look(mouse_delta);
+                } else if buttons.any_pressed([MouseButton::Right, MouseButton::Middle]) {
+                    // Keep whatever is at the focus under the cursor, however far away it is
+                    let rotation = controller.rotation();
+                    let pan = (rotation * Vec3::NEG_X * mouse_delta.x
+                        + rotation * Vec3::Y * mouse_delta.y)
+                        * controller.distance
+                        * 0.002;
+                    controller.focus += pan;
+                }
+                controller.distance = (controller.distance * SCROLL_FACTOR.powf(-scroll))
+                    .clamp(MIN_DISTANCE, MAX_DISTANCE);
+            }
+            CameraMode::Fly => {
+                if buttons.pressed(MouseButton::Right) {
+                    controller.look_around(mouse_delta);
+                }
+                controller.fly_speed =
+                    (controller.fly_speed * SCROLL_FACTOR.powf(scroll)).clamp(0.1, 1000.0);
+                if keyboard_free {
+                    let rotation = controller.rotation();
+                    let mut direction = Vec3::ZERO;
+                    for (key, axis) in [
+                        (KeyCode::W, Vec3::NEG_Z),
+                        (KeyCode::S, Vec3::Z),
+                        (KeyCode::A, Vec3::NEG_X),
+                        (KeyCode::D, Vec3::X),
+                    ] {
+                        if keys.pressed(key) {
+                            direction += rotation * axis;
+                        }
+                    }
+                    if keys.pressed(KeyCode::Q) {
+                        direction -= Vec3::Y;
+                    }
+                    if keys.pressed(KeyCode::E) {
+                        direction += Vec3::Y;
+                    }
+                    let fast = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                    let speed = controller.fly_speed * if fast { FAST_FACTOR } else { 1.0 };
+                    controller.focus +=
+                        direction.normalize_or_zero() * speed * time.delta_seconds();
+                }
+            }
+        }
+        *transform =
+            Transform::from_translation(controller.position()).with_rotation(controller.rotation());
+    }
+}
+
+fn camera_ui(
+    mut contexts: EguiContexts,
+    mut camera_ui: ResMut<CameraUi>,
+    mut ui_state: ResMut<UiState>,
+    mut cameras: Query<&mut CameraController>,
+) {
+    let Ok(mut controller) = cameras.get_single_mut() else {
+        return;
+    };
+    let mut edited = controller.clone();
+    let mut bookmarks = ui_state.camera_bookmarks.clone();
+    egui::Window::new("Camera")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("camera")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Mode");
+                    ui.horizontal(|ui| {
+                        for mode in [CameraMode::Orbit, CameraMode::Fly] {
+                            ui.radio_value(&mut edited.mode, mode, mode.label());
+                        }
+                        ui.label(format!("({TOGGLE_MODE_KEY:?} to switch)"));
+                    });
+                    ui.end_row();
+
+                    ui.label("Fly Speed");
+                    ui.add(
+                        egui::Slider::new(&mut edited.fly_speed, 0.1..=1000.0)
+                            .logarithmic(true)
+                            .suffix(" m/s"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Look Sensitivity");
+                    ui.add(
+                        egui::Slider::new(&mut edited.look_sensitivity, 0.001..=0.02)
+                            .logarithmic(true),
+                    );
+                    ui.end_row();
+                });
+
+            ui.separator();
+            ui.label("Bookmarks");
+            let mut removed = None;
+            for (index, bookmark) in bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.button("Go").clicked() {
+                        edited.set_view(bookmark.position, bookmark.focus);
+                    }
+                    if ui.button("Delete").clicked() {
+                        removed = Some(index);
+                    }
+                    ui.label(&bookmark.name);
+                });
+            }
+            if let Some(index) = removed {
+                bookmarks.remove(index);
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut camera_ui.bookmark_name);
+                let name = camera_ui.bookmark_name.trim();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Add Bookmark"))
+                    .clicked()
+                {
+                    bookmarks.push(CameraBookmark {
+                        name: name.to_string(),
+                        position: controller.position(),
+                        focus: controller.focus,
+                    });
+                    camera_ui.bookmark_name.clear();
+                }
+            });
+            ui.label("Bookmarks are saved with the preset.");
+        });
+
+    // Bookmarks are part of the preset, so they go through the UI state to be undoable
+    if bookmarks != ui_state.camera_bookmarks {
+        ui_state.camera_bookmarks = bookmarks;
+    }
+    if edited != *controller {
+        *controller = edited;
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+use crate::quality::WaterQuality;
+
+pub const USAGE: &str = "\
+Usage: bevy_water_shaders [OPTIONS]
+
+Options:
+  --method <NAME>              Water method to start with, e.g. SumOfSines, Fbm, Fft or Baked
+  --preset <FILE>              Preset file to start with, in place of the default one
+  --size <METRES>              Length of each side of the water
+  --resolution <QUADS>         Quads per metre of the water mesh
+  --seed <NUMBER>              Seed for the random waves, so runs can be repeated
+  --window <W>x<H>             Size of the window
+  --quality <TIER>             Low, Medium, High or Ultra
+  --benchmark                  Measure every method and quality tier, write a CSV report to
+                               benchmarks/ and exit
+
+Headless mode:
+  --headless                   Don't open a window, just write the --export or --bake files and exit
+  --export <FILE>              A mesh for .glb or .obj, or height and normal maps for .png or .exr
+  --bake <NAME>                Bake a flipbook into assets/bakes, as the bake window does
+  --time <SECONDS>             Water time to export, or to start the bake from
+  --region <X>,<Z>,<W>,<D>     Centre and size in metres of the area maps cover
+  --pixels <W>x<H>             Resolution of exported maps
+
+  -h, --help                   Print this help";
+
+/// The options the binary was started with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cli {
+    /// A `WaterMethod::NAME`, or `None` for the preset's method.
+    pub method: Option<String>,
+    pub preset: Option<PathBuf>,
+    /// In metres.
+    pub size: Option<f32>,
+    /// In quads per metre.
+    pub resolution: Option<f32>,
+    pub seed: Option<u64>,
+    pub window: Option<UVec2>,
+    pub quality: Option<WaterQuality>,
+    pub benchmark: bool,
+    pub headless: bool,
+    pub export: Option<PathBuf>,
+    /// Name of a flipbook to bake.
+    pub bake: Option<String>,
+    pub time: f64,
+    /// Area of the surface maps cover.
+    pub region: Option<Rect>,
+    pub pixels: Option<UVec2>,
+    pub help: bool,
+}
+
+impl Cli {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Cli::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--method" => cli.method = Some(value()?),
+                "--preset" => cli.preset = Some(PathBuf::from(value()?)),
+                "--size" => cli.size = Some(parse_positive(&arg, &value()?)?),
+                "--resolution" => cli.resolution = Some(parse_positive(&arg, &value()?)?),
+                "--seed" => cli.seed = Some(parse_number(&arg, &value()?)?),
+                "--window" => cli.window = Some(parse_size(&arg, &value()?)?),
+                "--quality" => {
+                    let value = value()?;
+                    cli.quality = Some(WaterQuality::from_label(&value).ok_or_else(|| {
+                        format!("{arg} expects Low, Medium, High or Ultra, not {value}")
+                    })?);
+                }
+                "--benchmark" => cli.benchmark = true,
+                "--headless" => cli.headless = true,
+                "--export" => cli.export = Some(PathBuf::from(value()?)),
+                "--bake" => cli.bake = Some(value()?),
+                "--time" => cli.time = parse_number(&arg, &value()?)?,
+                "--region" => {
+                    let value = value()?;
+                    let numbers = value
+                        .split(',')
+                        .map(|number| parse_number(&arg, number))
+                        .collect::<Result<Vec<f32>, _>>()?;
+                    let [x, z, width, depth] = numbers[..] else {
+                        return Err(format!("{arg} expects X,Z,WIDTH,DEPTH, not {value}"));
+                    };
+                    cli.region = Some(Rect::from_center_size(
+                        Vec2::new(x, z),
+                        Vec2::new(width, depth),
+                    ));
+                }
+                "--pixels" => cli.pixels = Some(parse_size(&arg, &value()?)?),
+                "-h" | "--help" => cli.help = true,
+                _ => return Err(format!("Unknown option {arg}")),
+            }
+        }
+        let output = cli.export.is_some() || cli.bake.is_some();
+        match (cli.headless, output) {
+            (true, false) => Err(String::from(
+                "--headless needs a file to --export or a flipbook to --bake",
+            )),
+            (false, true) => Err(String::from(
+                "--export and --bake only work with --headless",
+            )),
+            _ => Ok(cli),
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{arg} expects a number, not {value}"))
+}
+
+fn parse_positive(arg: &str, value: &str) -> Result<f32, String> {
+    let number: f32 = parse_number(arg, value)?;
+    if number > 0.0 {
+        Ok(number)
+    } else {
+        Err(format!("{arg} must be more than zero, not {value}"))
+    }
+}
+
+/// A size written as `WIDTHxHEIGHT`.
+fn parse_size(arg: &str, value: &str) -> Result<UVec2, String> {
+    let Some((width, height)) = value.split_once('x') else {
+        return Err(format!("{arg} expects WIDTHxHEIGHT, not {value}"));
+    };
+    Ok(UVec2::new(
+        parse_number(arg, width)?,
+        parse_number(arg, height)?,
+    ))
+}
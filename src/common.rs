@@ -1,6 +1,13 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
 
-#[derive(Debug, Clone)]
+/// Matches `DETAIL_WAVE_COUNT` in `water_functions.wgsl`.
+const DETAIL_WAVE_COUNT: usize = 3;
+
+#[derive(Component, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
 pub struct Shading {
     pub ambient: Color,
     pub diffuse_reflectance: Color,
@@ -9,9 +16,23 @@ pub struct Shading {
     pub fresnel: Fresnel,
     pub tip_color: Color,
     pub tip_attenuation: f32,
+    pub normal_mode: NormalMode,
+    pub detail_strength: f32,
+    pub detail_frequency: f32,
+}
+
+/// Where the fragment shader gets its surface normal from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum NormalMode {
+    /// Re-evaluate the waves for every pixel.
+    #[default]
+    PerPixel = 0,
+    /// Interpolate the vertex normals and layer cheap detail ripples on top.
+    VertexWithDetail = 1,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Fresnel {
     pub color: Color,
     pub bias: f32,
@@ -19,6 +40,17 @@ pub struct Fresnel {
     pub shininess: f32,
 }
 
+impl Shading {
+    /// Waves the fragment shader evaluates for each pixel, for a method that evaluates `per_pixel`
+    /// waves in the per-pixel normal mode.
+    pub fn fragment_wave_count(&self, per_pixel: u32) -> u32 {
+        match self.normal_mode {
+            NormalMode::PerPixel => per_pixel,
+            NormalMode::VertexWithDetail => DETAIL_WAVE_COUNT as u32,
+        }
+    }
+}
+
 impl Default for Shading {
     fn default() -> Self {
         Shading {
@@ -26,14 +58,229 @@ impl Default for Shading {
             diffuse_reflectance: Color::rgba_u8(0, 43, 77, 255),
             specular_reflectance: Color::WHITE,
             shininess: 2.0,
-            fresnel: Fresnel {
-                color: Color::WHITE,
-                bias: 0.24,
-                strength: 0.12,
-                shininess: 6.7,
-            },
+            fresnel: Fresnel::default(),
             tip_color: Color::WHITE,
             tip_attenuation: 6.0,
+            normal_mode: NormalMode::default(),
+            detail_strength: 0.15,
+            detail_frequency: 4.0,
+        }
+    }
+}
+
+impl Default for Fresnel {
+    fn default() -> Self {
+        Fresnel {
+            color: Color::WHITE,
+            bias: 0.24,
+            strength: 0.12,
+            shininess: 6.7,
+        }
+    }
+}
+
+/// How a material draws while one method cross-fades into another.
+///
+/// `amplitude` scales the material's displacement, and with it its normals and the shading that
+/// follows from them, so the outgoing method settles towards a flat surface and the incoming one
+/// rises out of it. By default the two take turns: the outgoing method flattens over the first
+/// half of the transition and the incoming one grows over the second, so they meet as the same
+/// flat surface where one hands over to the other. With dithering on, both are drawn the whole
+/// way through with complementary amplitudes, and each pixel has a fixed dither value in `0..1`
+/// and is drawn if it falls within `min..max`, so the two materials never draw the same pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct Fade {
+    pub min: f32,
+    pub max: f32,
+    pub amplitude: f32,
+}
+
+impl Fade {
+    pub const VISIBLE: Fade = Fade {
+        min: 0.0,
+        max: 1.0,
+        amplitude: 1.0,
+    };
+
+    /// The method being faded in, `progress` of the way through the transition.
+    pub fn incoming(progress: f32, dither: bool) -> Self {
+        if dither {
+            Fade {
+                min: 1.0 - progress,
+                max: 1.0,
+                amplitude: progress,
+            }
+        } else {
+            Fade::taking_turns(progress >= 0.5, 2.0 * progress - 1.0)
+        }
+    }
+
+    /// The method being faded out, `progress` of the way through the transition.
+    pub fn outgoing(progress: f32, dither: bool) -> Self {
+        if dither {
+            Fade {
+                min: 0.0,
+                max: 1.0 - progress,
+                amplitude: 1.0 - progress,
+            }
+        } else {
+            Fade::taking_turns(progress < 0.5, 1.0 - 2.0 * progress)
+        }
+    }
+
+    fn taking_turns(drawn: bool, amplitude: f32) -> Self {
+        Fade {
+            min: 0.0,
+            max: if drawn { 1.0 } else { 0.0 },
+            amplitude: amplitude.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Fade::VISIBLE
+    }
+}
+
+/// What the water shaders draw in place of the lit surface, to see what each term contributes.
+///
+/// Matches the `DEBUG_VIEW_*` constants in `water_debug.wgsl`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum DebugView {
+    #[default]
+    Shaded = 0,
+    /// World space normal, mapped from -1..1 to black..white per channel.
+    Normals = 1,
+    /// Height above the rest surface, blue below and red above.
+    Height = 2,
+    /// The Fresnel factor on its own, before the Fresnel color is applied.
+    Fresnel = 3,
+    SpecularOnly = 4,
+    DiffuseOnly = 5,
+    /// The color added to wave tips.
+    Tip = 6,
+    /// Where foam is: the baked foam texture for baked flipbooks, or for methods without foam,
+    /// where the surface bunches up enough for it.
+    FoamMask = 7,
+    /// How much the horizontal displacement stretches the surface: white where it's unchanged, red
+    /// where it's compressed, green where it's stretched and black where it folds over itself.
+    Jacobian = 8,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 9] = [
+        DebugView::Shaded,
+        DebugView::Normals,
+        DebugView::Height,
+        DebugView::Fresnel,
+        DebugView::SpecularOnly,
+        DebugView::DiffuseOnly,
+        DebugView::Tip,
+        DebugView::FoamMask,
+        DebugView::Jacobian,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "Shaded",
+            DebugView::Normals => "Normals",
+            DebugView::Height => "Height",
+            DebugView::Fresnel => "Fresnel",
+            DebugView::SpecularOnly => "Specular Only",
+            DebugView::DiffuseOnly => "Diffuse Only",
+            DebugView::Tip => "Tip",
+            DebugView::FoamMask => "Foam Mask",
+            DebugView::Jacobian => "Jacobian",
+        }
+    }
+}
+
+/// Debug drawing shared by every water material.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect)]
+pub struct DebugDraw {
+    pub view: DebugView,
+    /// Quads along each side of the tile mesh to outline, or zero for no wireframe.
+    pub wireframe_quads: u32,
+}
+
+/// How many waves a material's shaders sum up, for comparing the cost of methods. Methods with a
+/// level of detail count the most they ever use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WaveCounts {
+    pub vertex: u32,
+    pub fragment: u32,
+}
+
+/// The time a material animates to, which its waves' phases are worked out from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect)]
+pub struct WaveClock {
+    /// In seconds.
+    pub time: f64,
+    /// When set, every wave's speed is rounded to a whole number of turns per period, so the
+    /// whole surface repeats exactly after it. Waves too slow for one turn are sped up to one
+    /// rather than stopped.
+    pub loop_period: Option<f64>,
+}
+
+impl WaveClock {
+    /// Phase of a wave moving at `speed` radians per second, wrapped to `0..2π`.
+    ///
+    /// Worked out in f64 from the unwrapped time, so it keeps f32 precision however long the water
+    /// has run and moves continuously from one frame to the next.
+    pub fn phase(&self, speed: f32) -> f32 {
+        let speed = match self.loop_period {
+            Some(period) if period > 0.0 => {
+                let turn = TAU / period;
+                let turns = (speed as f64 / turn).round();
+                if turns == 0.0 && speed != 0.0 {
+                    (speed as f64).signum() * turn
+                } else {
+                    turns * turn
+                }
+            }
+            _ => speed as f64,
+        };
+        (self.time * speed).rem_euclid(TAU) as f32
+    }
+
+    /// Phases of the detail ripples in `detail_slope`, whose frequencies start at `frequency` and
+    /// whose speeds follow deep water dispersion.
+    pub fn detail_phases(&self, frequency: f32) -> Vec3 {
+        let mut phases = [0.0; DETAIL_WAVE_COUNT];
+        let mut frequency = frequency;
+        for phase in phases.iter_mut() {
+            *phase = self.phase((9.8 * frequency).sqrt());
+            frequency *= 1.9;
+        }
+        Vec3::from_array(phases)
+    }
+}
+
+/// WGSL modules imported by the water material shaders. Bevy only resolves `#import`s against
+/// shaders that have been loaded, so their handles are held for as long as the app runs.
+#[derive(Resource, Debug)]
+pub struct WaterShaderModules {
+    #[allow(dead_code)]
+    handles: Vec<Handle<Shader>>,
+}
+
+impl FromWorld for WaterShaderModules {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        WaterShaderModules {
+            handles: [
+                "shaders/water_functions.wgsl",
+                "shaders/water_lighting.wgsl",
+                "shaders/water_debug.wgsl",
+                "shaders/sum_water_functions.wgsl",
+                "shaders/fbm_water_functions.wgsl",
+                "shaders/fft_water_functions.wgsl",
+                "shaders/baked_water_functions.wgsl",
+            ]
+            .into_iter()
+            .map(|path| asset_server.load(path))
+            .collect(),
         }
     }
 }
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{common::DebugView, method::WaterMethodSet};
+
+/// What the water materials draw instead of, or on top of, the lit surface while tuning them.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct WaterDebug {
+    pub view: DebugView,
+    /// Outline the triangles of the tile mesh, after displacement.
+    pub wireframe: bool,
+}
+
+pub struct WaterDebugPlugin;
+
+impl Plugin for WaterDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterDebug>()
+            .register_type::<WaterDebug>()
+            .register_type::<DebugView>()
+            .add_systems(Update, debug_ui.before(WaterMethodSet::Update));
+    }
+}
+
+fn debug_ui(mut contexts: EguiContexts, mut debug: ResMut<WaterDebug>) {
+    let mut edited = debug.clone();
+    egui::Window::new("Debug View")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("debug_view")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("View");
+                    egui::ComboBox::from_id_source("debug_view")
+                        .selected_text(edited.view.label())
+                        .show_ui(ui, |ui| {
+                            for view in DebugView::ALL {
+                                ui.selectable_value(&mut edited.view, view, view.label());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Wireframe");
+                    ui.checkbox(&mut edited.wireframe, "");
+                    ui.end_row();
+                });
+        });
+    debug.set_if_neq(edited);
+}
@@ -0,0 +1,648 @@
+use bevy::{
+    asset::FileAssetIo,
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use bevy_egui::{egui, EguiContexts};
+use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgb32FImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bake::{self, BakeFormat},
+    common::WaveClock,
+    method::{MethodMaterial, WaterMethod, WaveMethod},
+    preset::PresetLibrary,
+    water_time::{WaterTime, WaterTimeOffset},
+    SelectedWater, PLANE_LENGTH, QUAD_RES,
+};
+
+const EXPORT_DIRECTORY: &str = "exports";
+/// Distance either side of a vertex the surface is sampled at to work out its normal, in metres.
+const NORMAL_SAMPLE_DISTANCE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Binary glTF.
+    #[default]
+    Gltf,
+    Obj,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 2] = [MeshFormat::Gltf, MeshFormat::Obj];
+
+    /// The format a path's extension asks for.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "glb" => Some(MeshFormat::Gltf),
+            "obj" => Some(MeshFormat::Obj),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "glb",
+            MeshFormat::Obj => "obj",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "glTF",
+            MeshFormat::Obj => "OBJ",
+        }
+    }
+}
+
+/// A square patch of the surface at one moment, as the `shape::Plane` grid the water is drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshExportSettings {
+    pub name: String,
+    pub format: MeshFormat,
+    /// Water time to export, in seconds.
+    pub time: f64,
+    /// Metres along each side.
+    pub size: f32,
+    /// Quads per metre.
+    pub resolution: f32,
+}
+
+impl Default for MeshExportSettings {
+    fn default() -> Self {
+        MeshExportSettings {
+            name: String::from("water"),
+            format: MeshFormat::default(),
+            time: 0.0,
+            size: PLANE_LENGTH,
+            resolution: QUAD_RES,
+        }
+    }
+}
+
+impl MeshExportSettings {
+    fn subdivisions(&self) -> u32 {
+        ((self.size * self.resolution).round() as u32).saturating_sub(1)
+    }
+}
+
+/// The displaced surface of a material at `clock`, centred on the origin of its surface frame.
+///
+/// The vertices are those of a `shape::Plane`, lifted by `height_at`, so it only needs the CPU.
+/// Normals come from the surface around each vertex rather than the mesh's faces.
+pub fn surface_mesh<M: WaterMethod>(
+    material: &M,
+    clock: WaveClock,
+    size: f32,
+    subdivisions: u32,
+) -> Mesh {
+    let mut material = material.clone();
+    material.set_clock(clock);
+    let mut mesh: Mesh = shape::Plane { size, subdivisions }.into();
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return mesh;
+    };
+    let mut normals = Vec::with_capacity(positions.len());
+    for position in positions.iter_mut() {
+        let surface_position = Vec2::new(position[0], position[2]);
+        let height = |offset: Vec2| material.height_at(surface_position + offset);
+        let slope = Vec2::new(
+            height(Vec2::X * NORMAL_SAMPLE_DISTANCE) - height(-Vec2::X * NORMAL_SAMPLE_DISTANCE),
+            height(Vec2::Y * NORMAL_SAMPLE_DISTANCE) - height(-Vec2::Y * NORMAL_SAMPLE_DISTANCE),
+        ) / (2.0 * NORMAL_SAMPLE_DISTANCE);
+        position[1] = height(Vec2::ZERO);
+        normals.push(Vec3::new(-slope.x, 1.0, -slope.y).normalize().to_array());
+    }
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh
+}
+
+/// Write the positions, normals and triangles of a mesh, in the format `path`'s extension asks for.
+pub fn write_mesh(mesh: &Mesh, path: &Path) -> io::Result<()> {
+    let format = MeshFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} isn't a .glb or .obj file", path.display()),
+        )
+    })?;
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32x3(normals)),
+        Some(Indices::U32(indices)),
+    ) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+        mesh.indices(),
+    )
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "mesh has no positions, normals or u32 indices",
+        ));
+    };
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    match format {
+        MeshFormat::Gltf => fs::write(path, glb(positions, normals, indices)),
+        MeshFormat::Obj => fs::write(path, obj(positions, normals, indices)),
+    }
+}
+
+fn obj(positions: &[[f32; 3]], normals: &[[f32; 3]], indices: &[u32]) -> String {
+    let mut obj = String::new();
+    for [x, y, z] in positions {
+        let _ = writeln!(obj, "v {x} {y} {z}");
+    }
+    for [x, y, z] in normals {
+        let _ = writeln!(obj, "vn {x} {y} {z}");
+    }
+    // OBJ indices start at one
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        let _ = writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}");
+    }
+    obj
+}
+
+/// A binary glTF holding one mesh, with everything in a single embedded buffer.
+fn glb(positions: &[[f32; 3]], normals: &[[f32; 3]], indices: &[u32]) -> Vec<u8> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut push_floats = |values: &[[f32; 3]]| {
+        let offset = buffer.len();
+        for value in values.iter().flatten() {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        (offset, buffer.len() - offset)
+    };
+    let (positions_offset, positions_length) = push_floats(positions);
+    let (normals_offset, normals_length) = push_floats(normals);
+    let indices_offset = buffer.len();
+    for index in indices {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+    let indices_length = buffer.len() - indices_offset;
+
+    // glTF needs the bounds of the positions
+    let (min, max) = positions.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), &position| (min.min(Vec3::from(position)), max.max(Vec3::from(position))),
+    );
+    let json = serde_json::json!({
+        "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "Water" }],
+        "meshes": [{
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1 },
+                "indices": 2,
+            }],
+        }],
+        "buffers": [{ "byteLength": buffer.len() }],
+        "bufferViews": [
+            {
+                "buffer": 0,
+                "byteOffset": positions_offset,
+                "byteLength": positions_length,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": normals_offset,
+                "byteLength": normals_length,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": indices_offset,
+                "byteLength": indices_length,
+                "target": ELEMENT_ARRAY_BUFFER,
+            },
+        ],
+        "accessors": [
+            {
+                "bufferView": 0,
+                "componentType": FLOAT,
+                "count": positions.len(),
+                "type": "VEC3",
+                "min": min.to_array(),
+                "max": max.to_array(),
+            },
+            {
+                "bufferView": 1,
+                "componentType": FLOAT,
+                "count": normals.len(),
+                "type": "VEC3",
+            },
+            {
+                "bufferView": 2,
+                "componentType": UNSIGNED_INT,
+                "count": indices.len(),
+                "type": "SCALAR",
+            },
+        ],
+    });
+
+    // Chunks are padded to four bytes, JSON with spaces and binary with zeros
+    let mut json = json.to_string().into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+    let length = 12 + 8 + json.len() + 8 + buffer.len();
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(&buffer);
+    glb
+}
+
+/// A rectangle of the surface at one moment, to write height and normal maps of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapExportSettings {
+    pub name: String,
+    pub format: BakeFormat,
+    /// Water time to export, in seconds.
+    pub time: f64,
+    /// Middle of the region, in metres along the surface.
+    pub center: Vec2,
+    /// Metres the region covers along the surface's x and z.
+    pub size: Vec2,
+    /// Pixels along the surface's x and z.
+    pub resolution: UVec2,
+}
+
+impl Default for MapExportSettings {
+    fn default() -> Self {
+        MapExportSettings {
+            name: String::from("water"),
+            format: BakeFormat::default(),
+            time: 0.0,
+            center: Vec2::ZERO,
+            size: Vec2::splat(PLANE_LENGTH),
+            resolution: UVec2::splat(1024),
+        }
+    }
+}
+
+/// Where a pair of maps was taken from, saved next to them as `<name>.ron`.
+///
+/// PNG height maps hold `0..1` across `height_min..height_max`, and PNG normal maps hold the
+/// normal mapped from `-1..1` to `0..1`. EXR maps hold heights in metres and normals as they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapMetadata {
+    pub time: f64,
+    pub center: Vec2,
+    pub size: Vec2,
+    pub height_min: f32,
+    pub height_max: f32,
+}
+
+/// Height and normal maps of the surface, ready to save.
+pub struct SurfaceMaps {
+    pub metadata: MapMetadata,
+    resolution: UVec2,
+    /// Pixels row by row, with rows running along the surface's z.
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+}
+
+/// Sample a material's surface at `clock` into height and normal maps. Like the other exports,
+/// it's worked out on the CPU from `height_at`, so callers turn away methods without
+/// `CPU_HEIGHT`.
+pub fn surface_maps<M: WaterMethod>(
+    material: &M,
+    clock: WaveClock,
+    center: Vec2,
+    size: Vec2,
+    resolution: UVec2,
+) -> SurfaceMaps {
+    let resolution = resolution.max(UVec2::ONE);
+    let mut material = material.clone();
+    material.set_clock(clock);
+    let texel_size = size / resolution.as_vec2();
+    let (heights, normals) =
+        bake::sample_surface(&material, center, texel_size, resolution.x, resolution.y);
+
+    // Heights are stored across the range the method can reach, so maps of different times
+    // compare directly. Methods that don't know theirs fall back to the range of this map.
+    let (min, max) = material.displacement_bounds();
+    let (height_min, height_max) = if max.y > min.y {
+        (min.y, max.y)
+    } else {
+        heights
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &height| {
+                (min.min(height), max.max(height))
+            })
+    };
+    SurfaceMaps {
+        metadata: MapMetadata {
+            time: clock.time,
+            center,
+            size,
+            height_min,
+            height_max,
+        },
+        resolution,
+        heights,
+        normals,
+    }
+}
+
+impl SurfaceMaps {
+    /// Write the height map to `path`, in the format its extension asks for, with the normal map
+    /// beside it as `<name>_normal` and the metadata as `<name>.ron`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let format = BakeFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} isn't a .png or .exr file", path.display()),
+            )
+        })?;
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let normal_path = path.with_file_name(format!("{name}_normal.{}", format.extension()));
+        let (width, height) = (self.resolution.x, self.resolution.y);
+        let index = |x: u32, y: u32| (y * width + x) as usize;
+
+        let (height_map, normal_map) = match format {
+            BakeFormat::Png => {
+                let range = self.metadata.height_max - self.metadata.height_min;
+                let to_u16 = |value: f32| (value.clamp(0.0, 1.0) * 65535.0).round() as u16;
+                (
+                    DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+                        let height = self.heights[index(x, y)] - self.metadata.height_min;
+                        Luma([to_u16(if range > 0.0 { height / range } else { 0.0 })])
+                    })),
+                    DynamicImage::ImageRgb16(ImageBuffer::from_fn(width, height, |x, y| {
+                        let normal = self.normals[index(x, y)] * 0.5 + 0.5;
+                        Rgb(normal.to_array().map(to_u16))
+                    })),
+                )
+            }
+            // The EXR encoder only takes RGB(A), so heights are repeated across RGB
+            BakeFormat::Exr => (
+                DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(width, height, |x, y| {
+                    Rgb([self.heights[index(x, y)]; 3])
+                })),
+                DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(width, height, |x, y| {
+                    Rgb(self.normals[index(x, y)].to_array())
+                })),
+            ),
+        };
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        bake::save_image(&height_map, path)?;
+        bake::save_image(&normal_map, &normal_path)?;
+        bake::save_ron(&self.metadata, &path.with_extension("ron"))
+    }
+}
+
+pub fn export_directory() -> PathBuf {
+    FileAssetIo::get_base_path().join(EXPORT_DIRECTORY)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportKind {
+    Mesh,
+    Maps,
+}
+
+/// The state of the export window, and an export it has asked for.
+#[derive(Resource, Debug, Default)]
+pub struct Exporter {
+    pub mesh: MeshExportSettings,
+    pub maps: MapExportSettings,
+    requested: Option<ExportKind>,
+    /// Outcome of the last export, shown in the window.
+    pub status: Option<String>,
+}
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Exporter>()
+            .add_systems(Update, export_ui);
+    }
+}
+
+fn time_row(ui: &mut egui::Ui, time: &mut f64, water_time: &WaterTime) {
+    ui.label("Time");
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(time).speed(0.01).suffix(" s"));
+        if ui.button("Now").clicked() {
+            *time = water_time.elapsed();
+        }
+    });
+    ui.end_row();
+}
+
+fn export_ui(
+    mut contexts: EguiContexts,
+    mut exporter: ResMut<Exporter>,
+    water_time: Res<WaterTime>,
+) {
+    let exporter = exporter.as_mut();
+    let mut requested = None;
+    egui::Window::new("Export")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.collapsing("Mesh", |ui| {
+                let settings = &mut exporter.mesh;
+                egui::Grid::new("export_mesh")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut settings.name);
+                        ui.end_row();
+
+                        ui.label("Format");
+                        ui.horizontal(|ui| {
+                            for format in MeshFormat::ALL {
+                                ui.radio_value(&mut settings.format, format, format.label());
+                            }
+                        });
+                        ui.end_row();
+
+                        time_row(ui, &mut settings.time, &water_time);
+
+                        ui.label("Size");
+                        ui.add(egui::Slider::new(&mut settings.size, 1.0..=500.0).suffix(" m"));
+                        ui.end_row();
+
+                        ui.label("Resolution");
+                        ui.add(
+                            egui::Slider::new(&mut settings.resolution, 0.5..=20.0)
+                                .suffix(" quads/m"),
+                        );
+                        ui.end_row();
+                    });
+                let valid_name = PresetLibrary::is_valid_name(&settings.name);
+                if ui
+                    .add_enabled(valid_name, egui::Button::new("Export Mesh"))
+                    .clicked()
+                {
+                    requested = Some(ExportKind::Mesh);
+                }
+            });
+
+            ui.collapsing("Height and Normal Maps", |ui| {
+                let settings = &mut exporter.maps;
+                egui::Grid::new("export_maps")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut settings.name);
+                        ui.end_row();
+
+                        ui.label("Format");
+                        ui.horizontal(|ui| {
+                            for format in BakeFormat::ALL {
+                                ui.radio_value(&mut settings.format, format, format.label());
+                            }
+                        });
+                        ui.end_row();
+
+                        time_row(ui, &mut settings.time, &water_time);
+
+                        ui.label("Center");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut settings.center.x).prefix("x: "));
+                            ui.add(egui::DragValue::new(&mut settings.center.y).prefix("z: "));
+                        });
+                        ui.end_row();
+
+                        ui.label("Size");
+                        ui.horizontal(|ui| {
+                            for size in [&mut settings.size.x, &mut settings.size.y] {
+                                ui.add(
+                                    egui::DragValue::new(size)
+                                        .clamp_range(0.1..=10000.0)
+                                        .suffix(" m"),
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Resolution");
+                        ui.horizontal(|ui| {
+                            for resolution in
+                                [&mut settings.resolution.x, &mut settings.resolution.y]
+                            {
+                                ui.add(
+                                    egui::DragValue::new(resolution)
+                                        .clamp_range(1..=8192)
+                                        .suffix(" px"),
+                                );
+                            }
+                        });
+                        ui.end_row();
+                    });
+                let valid_name = PresetLibrary::is_valid_name(&settings.name);
+                if ui
+                    .add_enabled(valid_name, egui::Button::new("Export Maps"))
+                    .clicked()
+                {
+                    requested = Some(ExportKind::Maps);
+                }
+            });
+
+            if let Some(status) = &exporter.status {
+                ui.label(status);
+            }
+        });
+
+    if requested.is_some() {
+        exporter.requested = requested;
+        exporter.status = None;
+    }
+}
+
+/// Export the selected water body if the export window asked for it and the body uses this
+/// method. Everything is in the body's own space, so it lines up once given the body's transform.
+pub fn export_selected_water<M: WaterMethod>(
+    mut exporter: ResMut<Exporter>,
+    water_time: Res<WaterTime>,
+    selected: Res<SelectedWater>,
+    bodies: Query<(&WaveMethod, &MethodMaterial<M>, Option<&WaterTimeOffset>)>,
+    materials: Res<Assets<M>>,
+) {
+    let Some(kind) = exporter.requested else {
+        return;
+    };
+    let Some((wave_method, material, offset)) =
+        selected.0.and_then(|entity| bodies.get(entity).ok())
+    else {
+        return;
+    };
+    let (true, Some(material)) = (wave_method.is::<M>(), materials.get(&material.0)) else {
+        return;
+    };
+    exporter.requested = None;
+    if !M::CPU_HEIGHT {
+        exporter.status = Some(format!("{} has no CPU heights to export", M::LABEL));
+        return;
+    }
+    let clock = |time: f64| WaveClock {
+        time: time + offset.map_or(0.0, |offset| offset.0 as f64),
+        loop_period: water_time.loop_period,
+    };
+    let (file_name, result) = match kind {
+        ExportKind::Mesh => {
+            let settings = &exporter.mesh;
+            let mesh = surface_mesh(
+                material,
+                clock(settings.time),
+                settings.size,
+                settings.subdivisions(),
+            );
+            let file_name = format!("{}.{}", settings.name, settings.format.extension());
+            let result = write_mesh(&mesh, &export_directory().join(&file_name));
+            (file_name, result)
+        }
+        ExportKind::Maps => {
+            let settings = &exporter.maps;
+            let maps = surface_maps(
+                material,
+                clock(settings.time),
+                settings.center,
+                settings.size,
+                settings.resolution,
+            );
+            let file_name = format!("{}.{}", settings.name, settings.format.extension());
+            let result = maps.save(&export_directory().join(&file_name));
+            (file_name, result)
+        }
+    };
+    exporter.status = Some(match result {
+        Ok(()) => format!("Exported {file_name}"),
+        Err(error) => format!("Export failed: {error}"),
+    });
+}
@@ -1,13 +1,32 @@
 use bevy::{
     prelude::*,
-    reflect::{TypePath, TypeUuid},
+    reflect::TypeUuid,
     render::{
         render_asset::RenderAssets,
         render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType},
     },
 };
+use bevy_egui::egui;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::{
+    camera::CameraController,
+    common,
+    method::{shows_method, MethodMaterial, MethodTransition, WaterMethod, WaveMethod},
+    preset::WaterPreset,
+    quality::QualitySettings,
+    weather::Weather,
+    UiState,
+};
+
+/// Octaves past this many are left out. The shader packs their phases four to a vector, in
+/// `MAX_OCTAVE_VECTORS` vectors.
+pub const MAX_OCTAVES: usize = 64;
+
+#[derive(Component, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
 pub struct FbmWaterConfig {
     // Vertex shader
     pub vertex_wave_count: usize,
@@ -37,6 +56,11 @@ pub struct FbmWaterConfig {
     pub fragment_height: f32,
     pub fragment_max_peak: f32,
     pub fragment_peak_offset: f32,
+    // Level of detail
+    pub lod_near: f32,
+    pub lod_far: f32,
+    pub vertex_min_wave_count: usize,
+    pub fragment_min_wave_count: usize,
 }
 
 impl Default for FbmWaterConfig {
@@ -68,29 +92,375 @@ impl Default for FbmWaterConfig {
             fragment_height: 1.0,
             fragment_max_peak: 1.0,
             fragment_peak_offset: 1.0,
+            lod_near: 10.0,
+            lod_far: 100.0,
+            vertex_min_wave_count: 8,
+            fragment_min_wave_count: 8,
+        }
+    }
+}
+
+impl FbmWaterConfig {
+    /// The config as the weather shapes it. Every octave's direction is stepped from the seed, so
+    /// offsetting the seeds turns the whole sea with the wind. Gusts roughen the fragment ripples.
+    pub fn with_weather(&self, weather: &Weather) -> Self {
+        FbmWaterConfig {
+            vertex_seed: self.vertex_seed + weather.wind_angle(),
+            vertex_initial_speed: self.vertex_initial_speed * weather.speed_scale(),
+            vertex_height: self.vertex_height * weather.height_scale(),
+            fragment_seed: self.fragment_seed + weather.wind_angle(),
+            fragment_initial_speed: self.fragment_initial_speed * weather.speed_scale(),
+            fragment_height: self.fragment_height * weather.gust_scale(),
+            ..self.clone()
         }
     }
 }
 
 /// "Fractional Brownian Motion" based water material.
-#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone, Default)]
+#[derive(AsBindGroup, TypeUuid, Reflect, Debug, Clone, PartialEq, Default)]
 #[uniform(0, FbmMaterialUniform)]
 #[uuid = "5f37d7f4-3403-4639-9d92-b4e5832e1514"]
 pub struct FbmWaterMaterial {
-    pub time: f32,
+    pub clock: common::WaveClock,
     pub fbm_config: FbmWaterConfig,
     pub shading: super::common::Shading,
+    pub fade: super::common::Fade,
+    pub debug_draw: super::common::DebugDraw,
+    pub quality: QualitySettings,
+    pub weather: Weather,
+    /// Where the vertex octaves' level of detail is measured from. It's the main camera in every
+    /// pass, so shadow and depth views displace the surface exactly as it's drawn.
+    pub lod_camera: Vec3,
 }
 
 impl FbmWaterMaterial {
     pub fn new() -> Self {
         FbmWaterMaterial::default()
     }
+
+    /// The config the shader actually draws with.
+    pub fn weathered_config(&self) -> FbmWaterConfig {
+        let mut config = self.fbm_config.with_weather(&self.weather);
+        config.vertex_wave_count += self.quality.fbm_extra_octaves;
+        config.fragment_wave_count += self.quality.fbm_extra_octaves;
+        // The shader only has room for this many octave phases
+        config.vertex_wave_count = config.vertex_wave_count.min(MAX_OCTAVES);
+        config.fragment_wave_count = config.fragment_wave_count.min(MAX_OCTAVES);
+        if let Some(octaves) = self.quality.fbm_vertex_octaves {
+            config.vertex_wave_count = config.vertex_wave_count.min(octaves);
+        }
+        if let Some(octaves) = self.quality.fbm_fragment_octaves {
+            config.fragment_wave_count = config.fragment_wave_count.min(octaves);
+        }
+        config.vertex_min_wave_count = config.vertex_min_wave_count.min(config.vertex_wave_count);
+        config.fragment_min_wave_count = config
+            .fragment_min_wave_count
+            .min(config.fragment_wave_count);
+        config
+    }
+}
+
+impl WaterMethod for FbmWaterMaterial {
+    const NAME: &'static str = "Fbm";
+    const LABEL: &'static str = "FBM";
+
+    fn new_material(_rng: &mut GlobalRng) -> Self {
+        FbmWaterMaterial::new()
+    }
+
+    fn set_clock(&mut self, clock: common::WaveClock) {
+        self.clock = clock;
+    }
+
+    fn apply_shading(&mut self, shading: &super::common::Shading) {
+        self.shading = shading.clone();
+    }
+
+    fn set_fade(&mut self, fade: super::common::Fade) {
+        self.fade = fade;
+    }
+
+    fn set_debug_draw(&mut self, debug_draw: super::common::DebugDraw) {
+        self.debug_draw = debug_draw;
+    }
+
+    fn set_quality(&mut self, quality: QualitySettings) {
+        self.quality = quality;
+    }
+
+    fn apply_weather(&mut self, weather: &Weather) {
+        self.weather = weather.clone();
+    }
+
+    fn displacement_bounds(&self) -> (Vec3, Vec3) {
+        let config = self.weathered_config();
+        let mut amplitude = config.vertex_amplitude;
+        let mut amplitude_sum = 0.0;
+        for _ in 0..config.vertex_wave_count {
+            amplitude_sum += amplitude.abs();
+            amplitude *= config.vertex_amplitude_mult;
+        }
+        // Each wave is `amplitude * exp(max_peak * sin(x) - peak_offset)`, and the vertex shader
+        // averages over the wave count rather than the amplitude sum.
+        let peak = (config.vertex_max_peak.abs() - config.vertex_peak_offset).exp();
+        let max_height = amplitude_sum * peak / config.vertex_wave_count.max(1) as f32
+            * config.vertex_height.abs();
+        (
+            Vec3::new(0.0, -max_height, 0.0),
+            Vec3::new(0.0, max_height, 0.0),
+        )
+    }
+
+    fn wave_counts(&self) -> common::WaveCounts {
+        let config = self.weathered_config();
+        common::WaveCounts {
+            vertex: config.vertex_wave_count as u32,
+            fragment: self
+                .shading
+                .fragment_wave_count(config.fragment_wave_count as u32),
+        }
+    }
+
+    /// Mirrors `vertex_fbm` with every octave included, as seen up close.
+    fn height_at(&self, surface_position: Vec2) -> f32 {
+        let config = self.weathered_config();
+        let mut frequency = config.vertex_frequency;
+        let mut amplitude = config.vertex_amplitude;
+        let mut speed = config.vertex_initial_speed;
+        let mut seed = config.vertex_seed;
+        let mut position = surface_position;
+
+        let mut height = 0.0;
+        for _ in 0..config.vertex_wave_count {
+            let direction = Vec2::new(seed.cos(), seed.sin());
+            let x = direction.dot(position) * frequency + self.clock.phase(speed);
+            let wave =
+                amplitude * (config.vertex_max_peak * x.sin() - config.vertex_peak_offset).exp();
+            height += wave;
+
+            let dx = config.vertex_max_peak * wave * x.cos();
+            position -= direction * dx * amplitude * config.vertex_drag;
+
+            frequency *= config.vertex_frequency_mult;
+            amplitude *= config.vertex_amplitude_mult;
+            speed *= config.vertex_speed_ramp;
+            seed += config.vertex_seed_iter;
+        }
+        height / config.vertex_wave_count.max(1) as f32 * config.vertex_height
+    }
+
+    fn apply_preset(&mut self, preset: &WaterPreset) {
+        self.fbm_config = preset.fbm.clone();
+    }
+
+    fn draw_ui(&mut self, ui: &mut egui::Ui, ui_state: &mut UiState, _rng: &mut GlobalRng) {
+        egui::CollapsingHeader::new("FBM Vertex Shader").show(ui, |ui| {
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_seed, 0.0..=300.0)
+                    .step_by(1.0)
+                    .text("Vertex Seed"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_seed_iter, 0.0..=2000.0)
+                    .step_by(10.0)
+                    .text("Vertex Seed Iterator"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_frequency, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Frequency"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_frequency_mult, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Frequency Mult"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_amplitude, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Amplitude"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_amplitude_mult, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Amplitude Mult"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_max_peak, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Max Peak"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_peak_offset, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Peak Offset"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_initial_speed, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Initial Speed"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_speed_ramp, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Speed Ramp"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_drag, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Drag"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.vertex_height, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Vertex Height"),
+            );
+        });
+        egui::CollapsingHeader::new("FBM Fragment Shader").show(ui, |ui| {
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_seed, 0.0..=300.0)
+                    .step_by(1.0)
+                    .text("Fragment Seed"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_seed_iter, 0.0..=2000.0)
+                    .step_by(10.0)
+                    .text("Fragment Seed Iterator"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_frequency, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Frequency"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_frequency_mult, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Frequency Mult"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_amplitude, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Amplitude"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_amplitude_mult, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Amplitude Mult"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_max_peak, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Max Peak"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_peak_offset, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Peak Offset"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_initial_speed, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Initial Speed"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_speed_ramp, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Speed Ramp"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_drag, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Drag"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.fragment_height, 0.0..=2.0)
+                    .step_by(0.1)
+                    .text("Fragment Height"),
+            );
+        });
+        egui::CollapsingHeader::new("FBM Level of Detail").show(ui, |ui| {
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.lod_near, 0.0..=200.0)
+                    .step_by(1.0)
+                    .text("LOD Near"),
+            );
+            ui.add(
+                egui::Slider::new(&mut ui_state.fbm_config.lod_far, 0.0..=500.0)
+                    .step_by(1.0)
+                    .text("LOD Far"),
+            );
+            ui.add(
+                egui::Slider::new(
+                    &mut ui_state.fbm_config.vertex_min_wave_count,
+                    0..=MAX_OCTAVES,
+                )
+                .text("Vertex Min Waves"),
+            );
+            ui.add(
+                egui::Slider::new(
+                    &mut ui_state.fbm_config.fragment_min_wave_count,
+                    0..=MAX_OCTAVES,
+                )
+                .text("Fragment Min Waves"),
+            );
+        });
+    }
+
+    fn build(app: &mut App) {
+        app.add_systems(Update, (apply_fbm_config, update_lod_camera));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_fbm_config(
+    mut fbm_materials: ResMut<Assets<FbmWaterMaterial>>,
+    bodies: Query<
+        (&MethodMaterial<FbmWaterMaterial>, &FbmWaterConfig),
+        Or<(
+            Changed<FbmWaterConfig>,
+            Added<MethodMaterial<FbmWaterMaterial>>,
+        )>,
+    >,
+) {
+    for (material, fbm_config) in bodies.iter() {
+        if let Some(material) = fbm_materials.get_mut(&material.0) {
+            material.fbm_config = fbm_config.clone();
+        }
+    }
+}
+
+/// Keep the level of detail of every FBM material on screen measured from the main camera.
+fn update_lod_camera(
+    mut fbm_materials: ResMut<Assets<FbmWaterMaterial>>,
+    bodies: Query<(
+        &WaveMethod,
+        Option<&MethodTransition>,
+        &MethodMaterial<FbmWaterMaterial>,
+    )>,
+    cameras: Query<&GlobalTransform, With<CameraController>>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+    let position = camera.translation();
+    for (wave_method, transition, material) in bodies.iter() {
+        if !shows_method::<FbmWaterMaterial>(wave_method, transition) {
+            continue;
+        }
+        let moved = fbm_materials
+            .get(&material.0)
+            .is_some_and(|material| material.lod_camera != position);
+        if moved {
+            if let Some(material) = fbm_materials.get_mut(&material.0) {
+                material.lod_camera = position;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, ShaderType)]
 struct FbmMaterialUniform {
-    time: f32,
+    detail_phases: Vec3,
     ambient: Color,
     diffuse_reflectance: Color,
     specular_reflectance: Color,
@@ -101,6 +471,9 @@ struct FbmMaterialUniform {
     fresnel_shininess: f32,
     tip_attenuation: f32,
     tip_color: Color,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
     vertex_wave_count: u32,
     vertex_seed: f32,
     vertex_seed_iter: f32,
@@ -127,12 +500,66 @@ struct FbmMaterialUniform {
     fragment_height: f32,
     fragment_max_peak: f32,
     fragment_peak_offset: f32,
+    lod_near: f32,
+    lod_far: f32,
+    lod_camera: Vec3,
+    vertex_min_wave_count: u32,
+    vertex_peak_mean: f32,
+    fragment_min_wave_count: u32,
+    fragment_peak_mean: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    vertex_phases: [Vec4; MAX_OCTAVES / 4],
+    fragment_phases: [Vec4; MAX_OCTAVES / 4],
+}
+
+/// Phase of each octave on `clock`, packed four to a vector for the shader. Octave speeds start at
+/// `initial_speed` and are multiplied by `speed_ramp` each time, as in `vertex_fbm`.
+fn octave_phases(
+    clock: &common::WaveClock,
+    wave_count: usize,
+    initial_speed: f32,
+    speed_ramp: f32,
+) -> [Vec4; MAX_OCTAVES / 4] {
+    let mut phases = [0.0; MAX_OCTAVES];
+    let mut speed = initial_speed;
+    for phase in phases.iter_mut().take(wave_count) {
+        *phase = clock.phase(speed);
+        speed *= speed_ramp;
+    }
+    std::array::from_fn(|index| Vec4::from_slice(&phases[index * 4..]))
+}
+
+/// Average value of `exp(max_peak * sin(x) - peak_offset)` over a period, which is what an octave
+/// fades towards once it drops out of the level of detail.
+fn peak_mean(max_peak: f32, peak_offset: f32) -> f32 {
+    (-peak_offset).exp() * bessel_i0(max_peak)
+}
+
+/// Modified Bessel function of the first kind, order zero, from its power series.
+fn bessel_i0(x: f32) -> f32 {
+    let quarter_x_squared = x * x * 0.25;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..32 {
+        term *= quarter_x_squared / (k * k) as f32;
+        sum += term;
+        if term < sum * f32::EPSILON {
+            break;
+        }
+    }
+    sum
 }
 
 impl AsBindGroupShaderType<FbmMaterialUniform> for FbmWaterMaterial {
     fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> FbmMaterialUniform {
+        let config = self.weathered_config();
         FbmMaterialUniform {
-            time: self.time,
+            detail_phases: self.clock.detail_phases(self.shading.detail_frequency),
             ambient: self.shading.ambient,
             diffuse_reflectance: self.shading.diffuse_reflectance,
             specular_reflectance: self.shading.specular_reflectance,
@@ -143,32 +570,60 @@ impl AsBindGroupShaderType<FbmMaterialUniform> for FbmWaterMaterial {
             fresnel_shininess: self.shading.fresnel.shininess,
             tip_attenuation: self.shading.tip_attenuation,
             tip_color: self.shading.tip_color,
-            vertex_wave_count: self.fbm_config.vertex_wave_count as u32,
-            vertex_seed: self.fbm_config.vertex_seed,
-            vertex_seed_iter: self.fbm_config.vertex_seed_iter,
-            vertex_frequency: self.fbm_config.vertex_frequency,
-            vertex_frequency_mult: self.fbm_config.vertex_frequency_mult,
-            vertex_amplitude: self.fbm_config.vertex_amplitude,
-            vertex_amplitude_mult: self.fbm_config.vertex_amplitude_mult,
-            vertex_initial_speed: self.fbm_config.vertex_initial_speed,
-            vertex_speed_ramp: self.fbm_config.vertex_speed_ramp,
-            vertex_drag: self.fbm_config.vertex_drag,
-            vertex_height: self.fbm_config.vertex_height,
-            vertex_max_peak: self.fbm_config.vertex_max_peak,
-            vertex_peak_offset: self.fbm_config.vertex_peak_offset,
-            fragment_wave_count: self.fbm_config.fragment_wave_count as u32,
-            fragment_seed: self.fbm_config.fragment_seed,
-            fragment_seed_iter: self.fbm_config.fragment_seed_iter,
-            fragment_frequency: self.fbm_config.fragment_frequency,
-            fragment_frequency_mult: self.fbm_config.fragment_frequency_mult,
-            fragment_amplitude: self.fbm_config.fragment_amplitude,
-            fragment_amplitude_mult: self.fbm_config.fragment_amplitude_mult,
-            fragment_initial_speed: self.fbm_config.fragment_initial_speed,
-            fragment_speed_ramp: self.fbm_config.fragment_speed_ramp,
-            fragment_drag: self.fbm_config.fragment_drag,
-            fragment_height: self.fbm_config.fragment_height,
-            fragment_max_peak: self.fbm_config.fragment_max_peak,
-            fragment_peak_offset: self.fbm_config.fragment_peak_offset,
+            normal_mode: self.shading.normal_mode as u32,
+            detail_strength: self.shading.detail_strength,
+            detail_frequency: self.shading.detail_frequency,
+            vertex_wave_count: config.vertex_wave_count as u32,
+            vertex_seed: config.vertex_seed,
+            vertex_seed_iter: config.vertex_seed_iter,
+            vertex_frequency: config.vertex_frequency,
+            vertex_frequency_mult: config.vertex_frequency_mult,
+            vertex_amplitude: config.vertex_amplitude,
+            vertex_amplitude_mult: config.vertex_amplitude_mult,
+            vertex_initial_speed: config.vertex_initial_speed,
+            vertex_speed_ramp: config.vertex_speed_ramp,
+            vertex_drag: config.vertex_drag,
+            vertex_height: config.vertex_height,
+            vertex_max_peak: config.vertex_max_peak,
+            vertex_peak_offset: config.vertex_peak_offset,
+            fragment_wave_count: config.fragment_wave_count as u32,
+            fragment_seed: config.fragment_seed,
+            fragment_seed_iter: config.fragment_seed_iter,
+            fragment_frequency: config.fragment_frequency,
+            fragment_frequency_mult: config.fragment_frequency_mult,
+            fragment_amplitude: config.fragment_amplitude,
+            fragment_amplitude_mult: config.fragment_amplitude_mult,
+            fragment_initial_speed: config.fragment_initial_speed,
+            fragment_speed_ramp: config.fragment_speed_ramp,
+            fragment_drag: config.fragment_drag,
+            fragment_height: config.fragment_height,
+            fragment_max_peak: config.fragment_max_peak,
+            fragment_peak_offset: config.fragment_peak_offset,
+            lod_near: config.lod_near,
+            lod_far: config.lod_far,
+            lod_camera: self.lod_camera,
+            vertex_min_wave_count: config.vertex_min_wave_count as u32,
+            vertex_peak_mean: peak_mean(config.vertex_max_peak, config.vertex_peak_offset),
+            fragment_min_wave_count: config.fragment_min_wave_count as u32,
+            fragment_peak_mean: peak_mean(config.fragment_max_peak, config.fragment_peak_offset),
+            fade_min: self.fade.min,
+            fade_max: self.fade.max,
+            fade_amplitude: self.fade.amplitude,
+            debug_view: self.debug_draw.view as u32,
+            wireframe_quads: self.debug_draw.wireframe_quads,
+            effects: self.quality.effects(),
+            vertex_phases: octave_phases(
+                &self.clock,
+                config.vertex_wave_count,
+                config.vertex_initial_speed,
+                config.vertex_speed_ramp,
+            ),
+            fragment_phases: octave_phases(
+                &self.clock,
+                config.fragment_wave_count,
+                config.fragment_initial_speed,
+                config.fragment_speed_ramp,
+            ),
         }
     }
 }
@@ -181,4 +636,12 @@ impl Material for FbmWaterMaterial {
     fn fragment_shader() -> ShaderRef {
         "shaders/fbm_water_material.wgsl".into()
     }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/fbm_water_prepass.wgsl".into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/fbm_water_prepass.wgsl".into()
+    }
 }
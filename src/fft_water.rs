@@ -1,17 +1,21 @@
 use bevy::{
     prelude::*,
-    reflect::{TypePath, TypeUuid},
+    reflect::TypeUuid,
     render::{
         render_asset::RenderAssets,
         render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType},
     },
 };
-use std::f32::consts::PI;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-const GRAVITY: f32 = 9.81;
+use crate::{
+    common, method::WaterMethod, preset::WaterPreset, quality::QualitySettings, weather::Weather,
+};
 
-#[derive(Debug, Clone)]
-struct SpectrumSettings {
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectrumSettings {
     pub scale: f32,
     pub wind_speed: f32,
     pub wind_direction: f32,
@@ -22,41 +26,6 @@ struct SpectrumSettings {
     pub short_waves_fade: f32,
 }
 
-#[derive(Debug, Clone)]
-struct SpectrumSettingsUniform {
-    pub scale: f32,
-    pub angle: f32,
-    pub spread_blend: f32,
-    pub swell: f32,
-    pub alpha: f32,
-    pub peak_omega: f32,
-    pub gamma: f32,
-    pub short_waves_fade: f32,
-}
-
-fn jonswap_alpha(fetch: f32, wind_speed: f32) -> f32 {
-    0.076 * (GRAVITY * fetch / wind_speed / wind_speed).powf(-0.22)
-}
-
-fn jonswap_peak_frequency(fetch: f32, wind_speed: f32) -> f32 {
-    22.0 * (wind_speed * fetch / GRAVITY / GRAVITY).powf(-0.33)
-}
-
-impl SpectrumSettingsUniform {
-    pub fn from_spectrum_settings(settings: &SpectrumSettings) -> Self {
-        SpectrumSettingsUniform {
-            scale: settings.scale,
-            angle: settings.wind_direction / 180.0 * PI,
-            spread_blend: settings.spread_blend,
-            swell: settings.swell.clamp(0.01, 1.0),
-            alpha: jonswap_alpha(settings.fetch, settings.wind_speed),
-            peak_omega: jonswap_peak_frequency(settings.fetch, settings.wind_speed),
-            gamma: settings.peak_enhancement,
-            short_waves_fade: settings.short_waves_fade,
-        }
-    }
-}
-
 impl Default for SpectrumSettings {
     fn default() -> Self {
         SpectrumSettings {
@@ -73,17 +42,83 @@ impl Default for SpectrumSettings {
 }
 
 /// "Fourier Transform" based water material.
-#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone, Default)]
+#[derive(AsBindGroup, TypeUuid, Reflect, Debug, Clone, PartialEq, Default)]
 #[uniform(0, WaterMaterialUniform)]
 #[uuid = "e90e7bbc-912b-4f10-8088-a4c7e46b9d10"]
 pub struct FftWaterMaterial {
-    pub time: f32,
+    pub clock: common::WaveClock,
     pub shading: super::common::Shading,
+    pub fade: super::common::Fade,
+    pub debug_draw: super::common::DebugDraw,
+    pub quality: QualitySettings,
+    pub spectrum: SpectrumSettings,
+}
+
+impl WaterMethod for FftWaterMaterial {
+    const NAME: &'static str = "Fft";
+    const LABEL: &'static str = "FFT";
+    const CPU_HEIGHT: bool = false;
+
+    fn new_material(_rng: &mut GlobalRng) -> Self {
+        FftWaterMaterial::default()
+    }
+
+    fn set_clock(&mut self, clock: common::WaveClock) {
+        self.clock = clock;
+    }
+
+    fn apply_shading(&mut self, shading: &super::common::Shading) {
+        self.shading = shading.clone();
+    }
+
+    fn set_fade(&mut self, fade: super::common::Fade) {
+        self.fade = fade;
+    }
+
+    fn set_debug_draw(&mut self, debug_draw: super::common::DebugDraw) {
+        self.debug_draw = debug_draw;
+    }
+
+    fn set_quality(&mut self, quality: QualitySettings) {
+        self.quality = quality;
+    }
+
+    // The spectrum is parameterised by the wind itself, so it takes the weather's as is
+    fn apply_weather(&mut self, weather: &Weather) {
+        self.spectrum.wind_speed = weather.wind_speed;
+        self.spectrum.wind_direction = weather.wind_direction;
+    }
+
+    fn displacement_bounds(&self) -> (Vec3, Vec3) {
+        // The spectrum isn't sampled by the shader yet, so the surface is flat.
+        (Vec3::ZERO, Vec3::ZERO)
+    }
+
+    fn wave_counts(&self) -> common::WaveCounts {
+        // Only the detail ripples, until the spectrum is sampled
+        common::WaveCounts {
+            vertex: 0,
+            fragment: self.shading.fragment_wave_count(0),
+        }
+    }
+
+    fn height_at(&self, _surface_position: Vec2) -> f32 {
+        0.0
+    }
+
+    // The wind stays the weather's
+    fn apply_preset(&mut self, preset: &WaterPreset) {
+        self.spectrum = SpectrumSettings {
+            wind_speed: self.spectrum.wind_speed,
+            wind_direction: self.spectrum.wind_direction,
+            ..preset.spectrum.clone()
+        };
+    }
 }
 
 #[derive(Debug, Clone, Default, ShaderType)]
 struct WaterMaterialUniform {
-    time: f32,
+    detail_phases: Vec3,
     ambient: Color,
     diffuse_reflectance: Color,
     specular_reflectance: Color,
@@ -94,12 +129,21 @@ struct WaterMaterialUniform {
     fresnel_shininess: f32,
     tip_attenuation: f32,
     tip_color: Color,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
 }
 
 impl AsBindGroupShaderType<WaterMaterialUniform> for FftWaterMaterial {
     fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> WaterMaterialUniform {
         WaterMaterialUniform {
-            time: self.time,
+            detail_phases: self.clock.detail_phases(self.shading.detail_frequency),
             ambient: self.shading.ambient,
             diffuse_reflectance: self.shading.diffuse_reflectance,
             specular_reflectance: self.shading.specular_reflectance,
@@ -110,6 +154,15 @@ impl AsBindGroupShaderType<WaterMaterialUniform> for FftWaterMaterial {
             fresnel_shininess: self.shading.fresnel.shininess,
             tip_attenuation: self.shading.tip_attenuation,
             tip_color: self.shading.tip_color,
+            normal_mode: self.shading.normal_mode as u32,
+            detail_strength: self.shading.detail_strength,
+            detail_frequency: self.shading.detail_frequency,
+            fade_min: self.fade.min,
+            fade_max: self.fade.max,
+            fade_amplitude: self.fade.amplitude,
+            debug_view: self.debug_draw.view as u32,
+            wireframe_quads: self.debug_draw.wireframe_quads,
+            effects: self.quality.effects(),
         }
     }
 }
@@ -122,4 +175,12 @@ impl Material for FftWaterMaterial {
     fn fragment_shader() -> ShaderRef {
         "shaders/fft_water_material.wgsl".into()
     }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/fft_water_prepass.wgsl".into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/fft_water_prepass.wgsl".into()
+    }
 }
@@ -0,0 +1,246 @@
+use bevy::{
+    core_pipeline::core_3d,
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraphApp, RenderGraphContext},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Render, RenderApp, RenderSet,
+    },
+};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Mutex,
+};
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, Features, Maintain, MapMode, QuerySet,
+    QuerySetDescriptor, QueryType,
+};
+
+const START_NODE: &str = "opaque_pass_timer_start";
+const END_NODE: &str = "opaque_pass_timer_end";
+/// Two u64 timestamps, one either side of the pass.
+const TIMESTAMPS_SIZE: u64 = 2 * std::mem::size_of::<u64>() as u64;
+
+// Where the timer is in measuring a frame. Only one measurement is in flight at a time, so frames
+// rendered while the previous one is read back go unmeasured.
+const IDLE: u8 = 0;
+const STARTED: u8 = 1;
+const RESOLVED: u8 = 2;
+const MAPPING: u8 = 3;
+const MAPPED: u8 = 4;
+
+/// How long the GPU spends on the main opaque pass, measured with timestamp queries where the GPU
+/// supports them. The water is drawn in that pass, but so is every other opaque mesh, so this is
+/// an upper bound on what the water costs rather than its cost alone.
+///
+/// It's shared with the render world, which fills it in a frame or two after the fact.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct GpuPassTime(Arc<Mutex<PassTimeReading>>);
+
+#[derive(Debug, Default)]
+struct PassTimeReading {
+    supported: bool,
+    milliseconds: Option<f32>,
+    /// Whether `milliseconds` hasn't been taken by `take_fresh` yet.
+    fresh: bool,
+}
+
+impl GpuPassTime {
+    pub fn supported(&self) -> bool {
+        self.0.lock().is_ok_and(|reading| reading.supported)
+    }
+
+    /// The latest measurement, if there has been one yet.
+    pub fn milliseconds(&self) -> Option<f32> {
+        self.0.lock().ok().and_then(|reading| reading.milliseconds)
+    }
+
+    /// The latest measurement if it hasn't been taken before. Only some frames are measured, so
+    /// this keeps each measurement from being counted more than once.
+    pub fn take_fresh(&self) -> Option<f32> {
+        let mut reading = self.0.lock().ok()?;
+        std::mem::take(&mut reading.fresh)
+            .then_some(reading.milliseconds)
+            .flatten()
+    }
+
+    fn set_supported(&self, supported: bool) {
+        if let Ok(mut reading) = self.0.lock() {
+            reading.supported = supported;
+        }
+    }
+
+    fn set_milliseconds(&self, milliseconds: f32) {
+        if let Ok(mut reading) = self.0.lock() {
+            reading.milliseconds = Some(milliseconds);
+            reading.fresh = true;
+        }
+    }
+}
+
+pub struct GpuTimingPlugin;
+
+impl Plugin for GpuTimingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GpuPassTime>();
+    }
+
+    // The render device only exists once every plugin has been built
+    fn finish(&self, app: &mut App) {
+        let pass_time = app.world.resource::<GpuPassTime>().clone();
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        let render_device = render_app.world.resource::<RenderDevice>();
+        let supported = render_device.features().contains(Features::TIMESTAMP_QUERY);
+        pass_time.set_supported(supported);
+        if !supported {
+            return;
+        }
+        let period = render_app
+            .world
+            .resource::<RenderQueue>()
+            .get_timestamp_period();
+        let timer = PassTimer::new(render_device, period, pass_time);
+        render_app
+            .insert_resource(timer)
+            .add_systems(Render, read_pass_timer.in_set(RenderSet::Cleanup))
+            .add_render_graph_node::<StartTimerNode>(core_3d::graph::NAME, START_NODE)
+            .add_render_graph_node::<EndTimerNode>(core_3d::graph::NAME, END_NODE)
+            .add_render_graph_edges(
+                core_3d::graph::NAME,
+                &[
+                    core_3d::graph::node::START_MAIN_PASS,
+                    START_NODE,
+                    core_3d::graph::node::MAIN_OPAQUE_PASS,
+                    END_NODE,
+                    core_3d::graph::node::MAIN_TRANSPARENT_PASS,
+                ],
+            );
+    }
+}
+
+#[derive(Resource)]
+struct PassTimer {
+    query_set: QuerySet,
+    /// Where the queries resolve to on the GPU.
+    resolve_buffer: Buffer,
+    /// Where they're copied to be read back on the CPU.
+    readback_buffer: Buffer,
+    state: Arc<AtomicU8>,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    pass_time: GpuPassTime,
+}
+
+impl PassTimer {
+    fn new(render_device: &RenderDevice, period: f32, pass_time: GpuPassTime) -> Self {
+        let device = render_device.wgpu_device();
+        PassTimer {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                label: Some("opaque_pass_timestamps"),
+                ty: QueryType::Timestamp,
+                count: 2,
+            }),
+            resolve_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("opaque_pass_timestamps_resolve"),
+                size: TIMESTAMPS_SIZE,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("opaque_pass_timestamps_readback"),
+                size: TIMESTAMPS_SIZE,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            state: Arc::new(AtomicU8::new(IDLE)),
+            period,
+            pass_time,
+        }
+    }
+
+    fn advance(&self, from: u8, to: u8) -> bool {
+        self.state
+            .compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+}
+
+#[derive(Default)]
+struct StartTimerNode;
+
+impl Node for StartTimerNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let timer = world.resource::<PassTimer>();
+        if timer.advance(IDLE, STARTED) {
+            render_context
+                .command_encoder()
+                .write_timestamp(&timer.query_set, 0);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct EndTimerNode;
+
+impl Node for EndTimerNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let timer = world.resource::<PassTimer>();
+        if timer.advance(STARTED, RESOLVED) {
+            let encoder = render_context.command_encoder();
+            encoder.write_timestamp(&timer.query_set, 1);
+            encoder.resolve_query_set(&timer.query_set, 0..2, &timer.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &timer.resolve_buffer,
+                0,
+                &timer.readback_buffer,
+                0,
+                TIMESTAMPS_SIZE,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Once a frame's timestamps have been submitted, read them back without waiting on the GPU.
+fn read_pass_timer(timer: Res<PassTimer>, render_device: Res<RenderDevice>) {
+    if timer.advance(RESOLVED, MAPPING) {
+        let state = timer.state.clone();
+        timer
+            .readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                state.store(
+                    if result.is_ok() { MAPPED } else { IDLE },
+                    Ordering::Release,
+                );
+            });
+    } else if timer.state.load(Ordering::Acquire) == MAPPED {
+        let ticks = {
+            let timestamps = timer.readback_buffer.slice(..).get_mapped_range();
+            let timestamp = |index: usize| {
+                let bytes = timestamps[index * 8..(index + 1) * 8].try_into().unwrap();
+                u64::from_le_bytes(bytes)
+            };
+            timestamp(1).saturating_sub(timestamp(0))
+        };
+        timer.readback_buffer.unmap();
+        timer
+            .pass_time
+            .set_milliseconds(ticks as f32 * timer.period / 1_000_000.0);
+        timer.state.store(IDLE, Ordering::Release);
+    }
+    render_device.wgpu_device().poll(Maintain::Poll);
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy_turborand::prelude::*;
+use std::{io, path::Path, sync::atomic::AtomicU32};
+
+use crate::{
+    bake::{self, BakeFormat},
+    cli::Cli,
+    common, export,
+    method::{WaterMethod, WaterMethods},
+    preset::{self, PresetLibrary, WaterPreset},
+    weather::Weather,
+    PLANE_LENGTH, QUAD_RES,
+};
+
+/// `run` for one method, kept with the method when it's registered so any method can be run
+/// headless.
+pub type RunHeadless = fn(&Cli, &WaterPreset, &mut GlobalRng) -> Result<(), String>;
+
+/// Do what `cli` asks for with a fresh material of method `M`, set up from `preset`.
+pub fn run<M: WaterMethod>(
+    cli: &Cli,
+    preset: &WaterPreset,
+    rng: &mut GlobalRng,
+) -> Result<(), String> {
+    let mut material = M::new_material(rng);
+    material.apply_shading(&preset.shading);
+    material.apply_preset(preset);
+    material.apply_weather(&Weather::default());
+    let clock = common::WaveClock {
+        time: cli.time,
+        loop_period: None,
+    };
+    if let Some(name) = &cli.bake {
+        bake_flipbook(cli, name, &material)?;
+    }
+    match &cli.export {
+        Some(_) if !M::CPU_HEIGHT => Err(format!("{} has no CPU heights to export", M::LABEL)),
+        Some(path) => export_surface(cli, path, clock, &material)
+            .map_err(|error| format!("Couldn't write {}: {error}", path.display())),
+        None => Ok(()),
+    }
+}
+
+/// Bake a flipbook with the bake window's default settings, starting at `cli.time`.
+fn bake_flipbook<M: WaterMethod>(cli: &Cli, name: &str, material: &M) -> Result<(), String> {
+    if !M::CPU_HEIGHT {
+        return Err(format!("{} has no CPU heights to bake", M::LABEL));
+    }
+    if !PresetLibrary::is_valid_name(name) {
+        return Err(format!("{name} isn't a valid bake name"));
+    }
+    let settings = bake::BakeSettings {
+        name: name.to_string(),
+        start_time: cli.time,
+        ..default()
+    };
+    let status = bake::bake_and_save(material, &settings, &AtomicU32::default())?;
+    println!("{status}");
+    Ok(())
+}
+
+/// Write the surface to `path`: height and normal maps for images, a mesh for anything else.
+fn export_surface<M: WaterMethod>(
+    cli: &Cli,
+    path: &Path,
+    clock: common::WaveClock,
+    material: &M,
+) -> io::Result<()> {
+    if BakeFormat::from_path(path).is_some() {
+        let defaults = export::MapExportSettings::default();
+        let region = cli
+            .region
+            .unwrap_or_else(|| Rect::from_center_size(defaults.center, defaults.size));
+        let maps = export::surface_maps(
+            material,
+            clock,
+            region.center(),
+            region.size(),
+            cli.pixels.unwrap_or(defaults.resolution),
+        );
+        maps.save(path)
+    } else {
+        let size = cli.size.unwrap_or(PLANE_LENGTH);
+        let resolution = cli.resolution.unwrap_or(QUAD_RES);
+        let subdivisions = ((size * resolution).round() as u32).saturating_sub(1);
+        let mesh = export::surface_mesh(material, clock, size, subdivisions);
+        export::write_mesh(&mesh, path)
+    }
+}
+
+/// Do what `cli` asks for without opening a window, with any of `methods`. Like the app, it starts
+/// from the default preset unless given another.
+pub fn run_method(cli: &Cli, methods: &WaterMethods) -> Result<(), String> {
+    let preset_path = cli
+        .preset
+        .clone()
+        .unwrap_or_else(preset::starting_preset_path);
+    let preset = WaterPreset::load(&preset_path)
+        .map_err(|error| format!("Couldn't read {}: {error}", preset_path.display()))?;
+    let method = cli
+        .method
+        .clone()
+        .unwrap_or_else(|| preset.method.name().to_string());
+    let mut rng = cli.seed.map_or_else(GlobalRng::new, GlobalRng::with_seed);
+    let info = methods.find(&method).ok_or_else(|| {
+        format!(
+            "Unknown method {method}, expected one of {}",
+            methods.names()
+        )
+    })?;
+    (info.run_headless)(cli, &preset, &mut rng)
+}
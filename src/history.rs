@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::{ui_state_update, UiState};
+
+/// Oldest undo steps are dropped past this many.
+const MAX_HISTORY: usize = 100;
+
+/// Undo/redo history of the settings window.
+///
+/// Edits are only recorded once the mouse is released, so dragging a slider or color picker
+/// becomes a single undo step.
+#[derive(Resource, Debug, Default)]
+pub struct UiHistory {
+    /// The last settled state, which is what an undo step returns to.
+    committed: UiState,
+    undo: Vec<UiState>,
+    redo: Vec<UiState>,
+}
+
+impl UiHistory {
+    pub fn can_undo(&self, ui_state: &UiState) -> bool {
+        !self.undo.is_empty() || *ui_state != self.committed
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget all history and start over from `ui_state`.
+    pub fn reset(&mut self, ui_state: &UiState) {
+        self.committed = ui_state.clone();
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Move the last settled state along to `ui_state` without recording an undo step, for
+    /// changes that aren't the user's own edits, such as a playing sequence.
+    pub fn follow(&mut self, ui_state: &UiState) {
+        self.committed = ui_state.clone();
+    }
+
+    /// Record `ui_state` as a new undo step if it has changed since the last one.
+    fn commit(&mut self, ui_state: &UiState) {
+        if *ui_state == self.committed {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.committed, ui_state.clone());
+        self.undo.push(previous);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, ui_state: &mut UiState) {
+        // An edit still in progress counts as its own step
+        self.commit(ui_state);
+        if let Some(previous) = self.undo.pop() {
+            let current = std::mem::replace(&mut self.committed, previous);
+            self.redo.push(current);
+            *ui_state = self.committed.clone();
+        }
+    }
+
+    pub fn redo(&mut self, ui_state: &mut UiState) {
+        if *ui_state != self.committed {
+            // Editing after an undo starts a new branch, so there's nothing left to redo
+            self.commit(ui_state);
+            return;
+        }
+        if let Some(next) = self.redo.pop() {
+            let current = std::mem::replace(&mut self.committed, next);
+            self.undo.push(current);
+            *ui_state = self.committed.clone();
+        }
+    }
+}
+
+pub struct UiHistoryPlugin;
+
+impl Plugin for UiHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiHistory>().add_systems(
+            Update,
+            (undo_shortcuts, record_history)
+                .chain()
+                .after(ui_state_update),
+        );
+    }
+}
+
+fn undo_shortcuts(
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    mut history: ResMut<UiHistory>,
+    mut ui_state: ResMut<UiState>,
+) {
+    // Leave Ctrl+Z to text fields while they're being edited
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !ctrl {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Z) {
+        if shift {
+            history.redo(&mut ui_state);
+        } else {
+            history.undo(&mut ui_state);
+        }
+    } else if keys.just_pressed(KeyCode::Y) {
+        history.redo(&mut ui_state);
+    }
+}
+
+fn record_history(
+    mut contexts: EguiContexts,
+    mut history: ResMut<UiHistory>,
+    ui_state: Res<UiState>,
+) {
+    if *ui_state == history.committed {
+        return;
+    }
+    // Wait for drags to finish so they coalesce into one step
+    if contexts.ctx_mut().input(|input| input.pointer.any_down()) {
+        return;
+    }
+    history.commit(&ui_state);
+}
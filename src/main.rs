@@ -1,201 +1,457 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::ChangeWatcher, pbr::CascadeShadowConfigBuilder, prelude::*, render::primitives::Aabb,
+    window::WindowResolution,
+};
 use bevy_egui::{
     egui::{self, Color32},
     EguiContexts, EguiPlugin,
 };
 use bevy_turborand::prelude::*;
+use std::time::Duration;
 
+mod bake;
+mod baked_water;
+mod camera;
+mod cli;
 mod common;
+mod debug;
+mod export;
 mod fbm_water;
 mod fft_water;
+mod gpu_timing;
+mod headless;
+mod history;
+mod method;
+mod performance;
+mod preset;
+mod quality;
+mod sequence;
 mod sum_water;
+mod water_time;
+mod weather;
+
+use method::{
+    MethodMaterial, MethodTransitionSettings, WaterMethods, WaterMethodsPlugin, WaterSurface,
+    WaveMethod,
+};
 
 const PLANE_LENGTH: f32 = 100.0;
 const QUAD_RES: f32 = 10.0;
+const TILES_PER_SIDE: u32 = 10;
+
+#[derive(Component, Debug, Default, Reflect)]
+#[reflect(Component)]
+struct Water;
 
-#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
-enum WaveMethod {
-    SumOfSines,
-    #[default]
-    Fbm,
-    Fft,
+/// Floats on whichever water surface it's closest to.
+#[derive(Component, Debug, Default, Reflect)]
+#[reflect(Component)]
+struct Buoy;
+
+/// Rotated and scaled water bodies used to check displacement follows the entity transform.
+#[derive(Component, Debug, Default, Reflect)]
+#[reflect(Component)]
+struct TransformTestWater;
+
+/// A single tile of the water surface, with the bounds of its undisplaced mesh.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component)]
+struct WaterTile {
+    flat_min: Vec3,
+    flat_max: Vec3,
 }
 
-#[derive(Component, Debug)]
-struct Water;
+impl WaterTile {
+    /// Grow the flat mesh bounds by the (min, max) displacement a material can produce.
+    ///
+    /// Displacement is in world units along the surface, so it's brought into the mesh's local
+    /// space by undoing the entity's scale.
+    fn displaced_aabb(&self, (min_offset, max_offset): (Vec3, Vec3), scale: Vec3) -> Aabb {
+        let (a, b) = (min_offset / scale, max_offset / scale);
+        Aabb::from_min_max(self.flat_min + a.min(b), self.flat_max + a.max(b))
+    }
+}
+
+/// How the scene is set up, which the command line can change.
+#[derive(Resource, Debug, Clone)]
+struct SceneSettings {
+    /// The method the sea is spawned with.
+    wave_method: WaveMethod,
+    /// Length of each side of the sea, in metres.
+    plane_length: f32,
+    /// Quads per metre of the water mesh.
+    quad_res: f32,
+}
 
 #[derive(Resource, Debug)]
-struct WaterMaterials {
-    sum: Handle<sum_water::SumWaterMaterial>,
-    fbm: Handle<fbm_water::FbmWaterMaterial>,
-    fft: Handle<fft_water::FftWaterMaterial>,
+struct WaterMesh {
+    mesh: Handle<Mesh>,
+    tile: WaterTile,
+    tile_length: f32,
+    /// Quads along each side of a tile.
+    quads_per_side: u32,
+}
+
+/// An independent body of water, such as a lake or the sea.
+///
+/// Its settings are the `WaveMethod`, `WaveType`, `Shading` and `FbmWaterConfig` components on
+/// the same entity, along with a `MethodMaterial` for every registered method. Its tiles are
+/// spawned as children.
+#[derive(Component, Debug, Default, Reflect)]
+#[reflect(Component)]
+struct WaterBody;
+
+/// The water body edited by the settings window.
+#[derive(Resource, Debug, Default)]
+struct SelectedWater(Option<Entity>);
+
+/// Every water method, for the app and headless runs alike.
+fn water_methods() -> WaterMethodsPlugin {
+    WaterMethodsPlugin::default()
+        .with_method::<sum_water::SumWaterMaterial>()
+        .with_method::<fbm_water::FbmWaterMaterial>()
+        .with_method::<fft_water::FftWaterMaterial>()
+        .with_method::<baked_water::BakedWaterMaterial>()
 }
 
 fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins,
-            RngPlugin::default(),
-            EguiPlugin,
-            MaterialPlugin::<sum_water::SumWaterMaterial>::default(),
-            MaterialPlugin::<fbm_water::FbmWaterMaterial>::default(),
-            MaterialPlugin::<fft_water::FftWaterMaterial>::default(),
-        ))
+    let cli = match cli::Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("{error}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if cli.headless {
+        if let Err(error) = headless::run_method(&cli, &water_methods().methods()) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = cli.preset.as_ref().filter(|path| !path.is_file()) {
+        eprintln!("No preset file at {}", path.display());
+        std::process::exit(1);
+    }
+
+    let rng_plugin = match cli.seed {
+        Some(seed) => RngPlugin::default().with_rng_seed(seed),
+        None => RngPlugin::default(),
+    };
+    let window = Window {
+        resolution: cli.window.map_or_else(WindowResolution::default, |size| {
+            WindowResolution::new(size.x as f32, size.y as f32)
+        }),
+        ..default()
+    };
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(AssetPlugin {
+                watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+                ..default()
+            })
+            .set(WindowPlugin {
+                primary_window: Some(window),
+                ..default()
+            }),
+        rng_plugin,
+        EguiPlugin,
+        camera::CameraControllerPlugin,
+        debug::WaterDebugPlugin,
+        gpu_timing::GpuTimingPlugin,
+        performance::PerformancePlugin,
+        quality::WaterQualityPlugin,
+        bake::BakePlugin,
+        export::ExportPlugin,
+        preset::WaterPresetPlugin,
+        history::UiHistoryPlugin,
+        sequence::WaterSequencePlugin,
+        water_time::WaterTimePlugin,
+        water_methods(),
+    ));
+
+    // Command line choices take over from the starting preset
+    let mut scene = SceneSettings {
+        wave_method: WaveMethod::default(),
+        plane_length: cli.size.unwrap_or(PLANE_LENGTH),
+        quad_res: cli.resolution.unwrap_or(QUAD_RES),
+    };
+    if let Some(path) = &cli.preset {
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        app.world
+            .resource_mut::<preset::ActivePreset>()
+            .load_file(&asset_server, path);
+    }
+    if let Some(name) = &cli.method {
+        let methods = app.world.resource::<WaterMethods>();
+        let Some(method) = methods.find(name) else {
+            eprintln!("Unknown method {name}, expected one of {}", methods.names());
+            std::process::exit(2);
+        };
+        scene.wave_method = WaveMethod::named(method.name);
+        app.world
+            .resource_mut::<preset::ActivePreset>()
+            .override_method(scene.wave_method.clone());
+    }
+
+    if let Some(quality) = cli.quality {
+        app.insert_resource(quality);
+    }
+    if cli.benchmark {
+        let mut benchmark = app.world.resource_mut::<performance::Benchmark>();
+        benchmark.exit_when_done = true;
+        benchmark.request();
+    }
+
+    app.insert_resource(scene)
         .insert_resource(Msaa::Sample4)
         .insert_resource(ClearColor(Color::rgb_u8(203, 180, 152)))
-        .insert_resource(sum_water::WaveType::default())
-        .insert_resource(WaveMethod::default())
         .insert_resource(UiState::default())
+        .init_resource::<SelectedWater>()
+        .init_resource::<common::WaterShaderModules>()
+        .register_type::<WaveMethod>()
+        .register_type::<Water>()
+        .register_type::<TransformTestWater>()
+        .register_type::<WaterTile>()
+        .register_type::<WaterBody>()
+        .register_type::<Buoy>()
+        .register_type::<common::Shading>()
+        .register_type::<common::Fresnel>()
+        .register_type::<common::NormalMode>()
+        .register_type::<sum_water::WaveType>()
+        .register_type::<sum_water::WaveSpec>()
+        .register_type::<[sum_water::WaveSpec; sum_water::WAVE_COUNT]>()
+        .register_type::<fbm_water::FbmWaterConfig>()
+        .register_type::<fft_water::SpectrumSettings>()
         .add_systems(Startup, setup)
         .add_systems(
             Update,
             (
-                update_time,
-                ui_system,
-                update_wave_type.run_if(resource_changed::<sum_water::WaveType>()),
-                update_wave_method.run_if(resource_changed::<WaveMethod>()),
+                (
+                    ui_system,
+                    load_selected_water.run_if(resource_changed::<SelectedWater>()),
+                    ui_state_update.run_if(resource_changed::<UiState>()),
+                )
+                    .chain(),
+                update_transform_test_scene.run_if(resource_changed::<UiState>()),
+                float_buoys,
                 bevy::window::close_on_esc,
             ),
         )
-        .add_systems(
-            Update,
-            ui_state_update.run_if(resource_changed::<UiState>()),
-        )
         .run();
 }
 
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
-    mut fbm_materials: ResMut<Assets<fbm_water::FbmWaterMaterial>>,
-    mut fft_materials: ResMut<Assets<fft_water::FftWaterMaterial>>,
-    mut global_rng: ResMut<GlobalRng>,
-    wave_type: Res<sum_water::WaveType>,
-    wave_method: Res<WaveMethod>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    mut selected: ResMut<SelectedWater>,
+    scene: Res<SceneSettings>,
+    quality: Res<quality::WaterQuality>,
 ) {
     // Camera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(PLANE_LENGTH * 0.5, 3.0, 0.0)
-            .looking_at(Vec3::ZERO, Vec3::Y),
+    let controller = camera::CameraController::overlooking(scene.plane_length);
+    commands.spawn((
+        Camera3dBundle {
+            transform: Transform::from_translation(controller.position())
+                .with_rotation(controller.rotation()),
+            ..default()
+        },
+        controller,
+    ));
+
+    // Sun, matching the fallback direction used by the water shaders
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 20000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::default().looking_to(Vec3::new(1.0, -1.0, 0.0), Vec3::Y),
+        cascade_shadow_config: CascadeShadowConfigBuilder {
+            maximum_distance: scene.plane_length,
+            ..default()
+        }
+        .into(),
+        ..default()
+    });
+
+    // Objects to cast shadows onto the water
+    let object_material = standard_materials.add(Color::rgb_u8(120, 72, 48).into());
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(shape::Box::new(1.0, 6.0, 1.0).into()),
+        material: object_material.clone(),
+        transform: Transform::from_xyz(38.0, 1.0, -2.5),
         ..default()
     });
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(
+                shape::UVSphere {
+                    radius: 1.0,
+                    ..default()
+                }
+                .into(),
+            ),
+            material: object_material,
+            transform: Transform::from_xyz(34.0, 2.5, 2.0),
+            ..default()
+        },
+        Buoy,
+    ));
 
     // Water
-    let mesh = meshes.add(
-        shape::Plane {
-            size: PLANE_LENGTH,
-            subdivisions: (PLANE_LENGTH * QUAD_RES).round() as u32,
-        }
-        .into(),
+    let tile_length = scene.plane_length / TILES_PER_SIDE as f32;
+    let (tile_mesh, quads_per_side) = tile_mesh(
+        tile_length,
+        scene.quad_res * quality.settings().mesh_density,
     );
-    let sum_water_material = sum_materials.add(sum_water::SumWaterMaterial::random(
-        *wave_type,
-        global_rng.as_mut(),
-    ));
-    let fbm_water_material = fbm_materials.add(fbm_water::FbmWaterMaterial::new());
-    let fft_water_material = fft_materials.add(fft_water::FftWaterMaterial::default());
-    commands.insert_resource(WaterMaterials {
-        sum: sum_water_material.clone(),
-        fbm: fbm_water_material.clone(),
-        fft: fft_water_material.clone(),
-    });
-    match wave_method.as_ref() {
-        WaveMethod::SumOfSines => commands.spawn((
-            Water,
-            MaterialMeshBundle {
-                mesh,
-                material: sum_water_material,
-                ..default()
-            },
-        )),
-        WaveMethod::Fbm => commands.spawn((
-            Water,
-            MaterialMeshBundle {
-                mesh,
-                material: fbm_water_material,
-                ..default()
-            },
-        )),
-        WaveMethod::Fft => commands.spawn((
-            Water,
-            MaterialMeshBundle {
-                mesh,
-                material: fft_water_material,
-                ..default()
-            },
-        )),
+    let flat_aabb = tile_mesh.compute_aabb().unwrap_or_default();
+    let water_mesh = WaterMesh {
+        mesh: meshes.add(tile_mesh),
+        tile: WaterTile {
+            flat_min: flat_aabb.min().into(),
+            flat_max: flat_aabb.max().into(),
+        },
+        tile_length,
+        quads_per_side,
     };
+
+    let sea = spawn_water_body(
+        &mut commands,
+        &water_mesh,
+        "Sea",
+        scene.wave_method.clone(),
+        Transform::default(),
+        TILES_PER_SIDE,
+    );
+    selected.0 = Some(sea);
+
+    commands.insert_resource(water_mesh);
 }
 
-fn update_time(
-    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
-    mut fbm_materials: ResMut<Assets<fbm_water::FbmWaterMaterial>>,
-    mut fft_materials: ResMut<Assets<fft_water::FftWaterMaterial>>,
-    time: Res<Time>,
-) {
-    for material in sum_materials.iter_mut() {
-        material.1.time = time.elapsed_seconds_wrapped();
-    }
-    for material in fbm_materials.iter_mut() {
-        material.1.time = time.elapsed_seconds_wrapped();
-    }
-    for material in fft_materials.iter_mut() {
-        material.1.time = time.elapsed_seconds_wrapped();
+/// A flat water tile `tile_length` metres across with `quad_res` quads per metre, and how many
+/// quads it has along each side.
+fn tile_mesh(tile_length: f32, quad_res: f32) -> (Mesh, u32) {
+    let subdivisions = (tile_length * quad_res).round() as u32;
+    let mesh = shape::Plane {
+        size: tile_length,
+        subdivisions,
     }
+    .into();
+    (mesh, subdivisions + 1)
 }
 
-fn update_wave_type(
-    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
-    wave_type: Res<sum_water::WaveType>,
-) {
-    for material in sum_materials.iter_mut() {
-        for wave in material.1.waves.iter_mut() {
-            wave.ty = *wave_type;
-        }
-    }
+/// Spawn a water body made of `tiles_per_side` x `tiles_per_side` tiles centered on `transform`,
+/// with default settings and the given method. Its materials are added by each registered method.
+fn spawn_water_body(
+    commands: &mut Commands,
+    water_mesh: &WaterMesh,
+    name: &str,
+    wave_method: WaveMethod,
+    transform: Transform,
+    tiles_per_side: u32,
+) -> Entity {
+    let half_length = tiles_per_side as f32 * water_mesh.tile_length * 0.5;
+    commands
+        .spawn((
+            Name::new(name.to_string()),
+            WaterBody,
+            wave_method,
+            sum_water::WaveType::default(),
+            common::Shading::default(),
+            fbm_water::FbmWaterConfig::default(),
+            SpatialBundle::from_transform(transform),
+        ))
+        .with_children(|parent| {
+            for x in 0..tiles_per_side {
+                for z in 0..tiles_per_side {
+                    let translation = Vec3::new(
+                        (x as f32 + 0.5) * water_mesh.tile_length - half_length,
+                        0.0,
+                        (z as f32 + 0.5) * water_mesh.tile_length - half_length,
+                    );
+                    spawn_water_tile(parent, water_mesh, Transform::from_translation(translation));
+                }
+            }
+        })
+        .id()
 }
 
-fn update_wave_method(
+/// Spawn a single water tile. It's given its body's material for the current method once that
+/// exists, and its bounds are then grown to fit the displacement.
+fn spawn_water_tile(parent: &mut ChildBuilder, water_mesh: &WaterMesh, transform: Transform) {
+    parent.spawn((
+        Water,
+        water_mesh.tile,
+        Aabb::from_min_max(water_mesh.tile.flat_min, water_mesh.tile.flat_max),
+        water_mesh.mesh.clone(),
+        SpatialBundle::from_transform(transform),
+    ));
+}
+
+fn update_transform_test_scene(
     mut commands: Commands,
-    wave_method: Res<WaveMethod>,
-    water_query: Query<Entity, With<Water>>,
-    water_materials: Res<WaterMaterials>,
+    ui_state: Res<UiState>,
+    water_mesh: Res<WaterMesh>,
+    mut selected: ResMut<SelectedWater>,
+    test_query: Query<Entity, With<TransformTestWater>>,
 ) {
-    for entity in water_query.iter() {
-        match *wave_method {
-            WaveMethod::SumOfSines => {
-                commands
-                    .entity(entity)
-                    .remove::<Handle<fbm_water::FbmWaterMaterial>>();
-                commands
-                    .entity(entity)
-                    .remove::<Handle<fft_water::FftWaterMaterial>>();
-                commands.entity(entity).insert(water_materials.sum.clone());
-            }
-            WaveMethod::Fbm => {
-                commands
-                    .entity(entity)
-                    .remove::<Handle<sum_water::SumWaterMaterial>>();
-                commands
-                    .entity(entity)
-                    .remove::<Handle<fft_water::FftWaterMaterial>>();
-                commands.entity(entity).insert(water_materials.fbm.clone());
-            }
-            WaveMethod::Fft => {
-                commands
-                    .entity(entity)
-                    .remove::<Handle<sum_water::SumWaterMaterial>>();
-                commands
-                    .entity(entity)
-                    .remove::<Handle<fbm_water::FbmWaterMaterial>>();
-                commands.entity(entity).insert(water_materials.fft.clone());
+    let spawned = !test_query.is_empty();
+    if ui_state.transform_test_scene && !spawned {
+        let bodies = [
+            // A tilted lake
+            (
+                "Tilted Lake",
+                Transform::from_xyz(20.0, 6.0, -12.0)
+                    .with_rotation(Quat::from_rotation_z(0.4) * Quat::from_rotation_x(0.3)),
+            ),
+            // A squashed and stretched pond, whose waves shouldn't be scaled with it
+            (
+                "Scaled Pond",
+                Transform::from_xyz(20.0, 2.0, 12.0).with_scale(Vec3::new(0.5, 3.0, 1.5)),
+            ),
+        ];
+        for (name, transform) in bodies {
+            let water = spawn_water_body(
+                &mut commands,
+                &water_mesh,
+                name,
+                ui_state.wave_method.clone(),
+                transform,
+                1,
+            );
+            commands.entity(water).insert(TransformTestWater);
+        }
+    } else if !ui_state.transform_test_scene && spawned {
+        for entity in test_query.iter() {
+            if selected.0 == Some(entity) {
+                selected.0 = None;
             }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Keep buoys half submerged in the nearest water surface.
+fn float_buoys(mut buoys: Query<&mut Transform, With<Buoy>>, surfaces: Query<&WaterSurface>) {
+    for mut transform in buoys.iter_mut() {
+        let position = transform.translation;
+        let nearest = surfaces
+            .iter()
+            .min_by(|a, b| a.depth(position).abs().total_cmp(&b.depth(position).abs()));
+        if let Some(surface) = nearest {
+            transform.translation = surface.surface_point(position);
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 struct Colors {
     ambient: Color32,
     diffuse: Color32,
@@ -205,11 +461,28 @@ struct Colors {
 
 impl Default for Colors {
     fn default() -> Self {
-        let shading_default = common::Shading::default();
-        let ambient = shading_default.ambient.as_rgba_u8();
-        let diffuse = shading_default.diffuse_reflectance.as_rgba_u8();
-        let specular = shading_default.specular_reflectance.as_rgba_u8();
-        let tip = shading_default.tip_color.as_rgba_u8();
+        Colors::from(&common::Shading::default())
+    }
+}
+
+impl Colors {
+    /// Write these colors into the matching fields of `shading`.
+    fn apply(&self, shading: &mut common::Shading) {
+        shading.ambient = Color::rgb_u8(self.ambient.r(), self.ambient.g(), self.ambient.b());
+        shading.diffuse_reflectance =
+            Color::rgb_u8(self.diffuse.r(), self.diffuse.g(), self.diffuse.b());
+        shading.specular_reflectance =
+            Color::rgb_u8(self.specular.r(), self.specular.g(), self.specular.b());
+        shading.tip_color = Color::rgb_u8(self.tip.r(), self.tip.g(), self.tip.b());
+    }
+}
+
+impl From<&common::Shading> for Colors {
+    fn from(shading: &common::Shading) -> Self {
+        let ambient = shading.ambient.as_rgba_u8();
+        let diffuse = shading.diffuse_reflectance.as_rgba_u8();
+        let specular = shading.specular_reflectance.as_rgba_u8();
+        let tip = shading.tip_color.as_rgba_u8();
         Colors {
             ambient: Color32::from_rgb(ambient[0], ambient[1], ambient[2]),
             diffuse: Color32::from_rgb(diffuse[0], diffuse[1], diffuse[2]),
@@ -219,23 +492,249 @@ impl Default for Colors {
     }
 }
 
-#[derive(Resource, Debug, Default)]
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
 struct UiState {
     wave_method: WaveMethod,
     wave_type: sum_water::WaveType,
     shading: common::Shading,
     colors: Colors,
     fbm_config: fbm_water::FbmWaterConfig,
+    camera_bookmarks: Vec<camera::CameraBookmark>,
+    transform_test_scene: bool,
 }
 
+impl UiState {
+    /// The shading settings, including the colors edited through the color pickers.
+    fn shading(&self) -> common::Shading {
+        let mut shading = self.shading.clone();
+        self.colors.apply(&mut shading);
+        shading
+    }
+
+    fn to_preset(
+        &self,
+        sum_material: Option<&sum_water::SumWaterMaterial>,
+        fft_material: Option<&fft_water::FftWaterMaterial>,
+    ) -> preset::WaterPreset {
+        preset::WaterPreset {
+            method: self.wave_method.clone(),
+            shading: self.shading(),
+            wave_type: self.wave_type,
+            waves: sum_material.map(|material| material.waves.clone()),
+            fbm: self.fbm_config.clone(),
+            spectrum: fft_material
+                .map(|material| material.spectrum.clone())
+                .unwrap_or_default(),
+            cameras: self.camera_bookmarks.clone(),
+        }
+    }
+
+    fn apply_preset(&mut self, preset: &preset::WaterPreset) {
+        self.wave_method = preset.method.clone();
+        self.wave_type = preset.wave_type;
+        self.shading = preset.shading.clone();
+        self.colors = Colors::from(&preset.shading);
+        self.fbm_config = preset.fbm.clone();
+        self.camera_bookmarks = preset.cameras.clone();
+    }
+}
+
+/// Whether the live settings have drifted from the preset they were loaded from.
+fn preset_is_dirty(preset: &preset::WaterPreset, current: &preset::WaterPreset) -> bool {
+    let mut reference = preset.clone();
+    // Colors go through the 8-bit color pickers, so compare against what they'd show
+    Colors::from(&reference.shading).apply(&mut reference.shading);
+    if reference.waves.is_none() {
+        reference.waves = current.waves.clone();
+    }
+    reference != *current
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn ui_system(
+    mut commands: Commands,
     mut ui_state: ResMut<UiState>,
     mut contexts: EguiContexts,
-    wave_method: Res<WaveMethod>,
-    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
-    mut global_rng: ResMut<GlobalRng>,
+    sum_materials: Res<Assets<sum_water::SumWaterMaterial>>,
+    fft_materials: Res<Assets<fft_water::FftWaterMaterial>>,
+    methods: Res<WaterMethods>,
+    mut transition_settings: ResMut<MethodTransitionSettings>,
+    mut selected: ResMut<SelectedWater>,
+    bodies: Query<
+        (
+            Entity,
+            &Name,
+            Option<&MethodMaterial<sum_water::SumWaterMaterial>>,
+            Option<&MethodMaterial<fft_water::FftWaterMaterial>>,
+        ),
+        With<WaterBody>,
+    >,
+    asset_server: Res<AssetServer>,
+    mut active_preset: ResMut<preset::ActivePreset>,
+    mut presets: ResMut<Assets<preset::WaterPreset>>,
+    mut library: ResMut<preset::PresetLibrary>,
+    mut history: ResMut<history::UiHistory>,
+    mut weather: ResMut<weather::Weather>,
 ) {
+    let selected_body = selected.0.and_then(|entity| bodies.get(entity).ok());
+    let selected_name =
+        selected_body.map_or_else(|| "(none)".to_string(), |(_, name, ..)| name.to_string());
+    let sum_material = selected_body
+        .and_then(|(_, _, sum, _)| sum)
+        .and_then(|material| sum_materials.get(&material.0));
+    let fft_material = selected_body
+        .and_then(|(.., fft)| fft)
+        .and_then(|material| fft_materials.get(&material.0));
+
     egui::Window::new("Settings").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Water Body");
+            egui::ComboBox::from_id_source("water_body")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (entity, name, ..) in bodies.iter() {
+                        let is_selected = selected.0 == Some(entity);
+                        if ui.selectable_label(is_selected, name.as_str()).clicked() && !is_selected
+                        {
+                            selected.0 = Some(entity);
+                        }
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            let can_undo = history.can_undo(&ui_state);
+            if ui
+                .add_enabled(can_undo, egui::Button::new("Undo"))
+                .on_hover_text("Ctrl+Z")
+                .clicked()
+            {
+                history.undo(&mut ui_state);
+            }
+            if ui
+                .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+                .on_hover_text("Ctrl+Shift+Z")
+                .clicked()
+            {
+                history.redo(&mut ui_state);
+            }
+        });
+
+        // Presets
+        egui::CollapsingHeader::new("Presets").show(ui, |ui| {
+            let current = ui_state.to_preset(sum_material, fft_material);
+            let dirty = match presets.get(&active_preset.handle) {
+                Some(preset) => preset_is_dirty(preset, &current),
+                None => true,
+            };
+            ui.label(format!(
+                "Active: {}{}",
+                active_preset.name.as_deref().unwrap_or("(unsaved)"),
+                if dirty { " *" } else { "" }
+            ));
+
+            egui::ScrollArea::vertical()
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for name in library.names.clone() {
+                        let selected = library.selected.as_ref() == Some(&name);
+                        if ui.selectable_label(selected, &name).clicked() {
+                            library.save_name = name.clone();
+                            library.selected = Some(name);
+                        }
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let selected = library.selected.clone();
+                ui.add_enabled_ui(selected.is_some(), |ui| {
+                    if ui.button("Load").clicked() {
+                        if let Some(name) = &selected {
+                            active_preset.load(&asset_server, name);
+                            library.status = None;
+                        }
+                    }
+                    if ui
+                        .add_enabled(selected_body.is_some(), egui::Button::new("Tween To"))
+                        .on_hover_text("Play a sequence from the current settings to this preset")
+                        .clicked()
+                    {
+                        if let (Some(name), Some((entity, ..))) = (&selected, selected_body) {
+                            library.status = Some(match library.read(name) {
+                                Ok(preset) => {
+                                    let tween = sequence::WaterSequence::tween(
+                                        current.clone(),
+                                        preset,
+                                        sequence::KEYFRAME_SPACING,
+                                        sequence::Easing::EaseInOut,
+                                    );
+                                    commands.entity(entity).insert(tween.playing());
+                                    format!("Tweening to {name}")
+                                }
+                                Err(error) => format!("Couldn't read {name}: {error}"),
+                            });
+                        }
+                    }
+                    if ui.button("Duplicate").clicked() {
+                        if let Some(name) = &selected {
+                            library.status = Some(match library.duplicate(name) {
+                                Ok(copy_name) => {
+                                    let status = format!("Duplicated to {copy_name}");
+                                    library.selected = Some(copy_name);
+                                    status
+                                }
+                                Err(error) => format!("Couldn't duplicate {name}: {error}"),
+                            });
+                        }
+                    }
+                    let built_in = selected
+                        .as_deref()
+                        .is_some_and(preset::PresetLibrary::is_built_in);
+                    if ui
+                        .add_enabled(!built_in, egui::Button::new("Delete"))
+                        .on_disabled_hover_text("Built-in presets can't be deleted")
+                        .clicked()
+                    {
+                        if let Some(name) = &selected {
+                            library.status = Some(match library.delete(name) {
+                                Ok(()) => {
+                                    if active_preset.name.as_ref() == Some(name) {
+                                        active_preset.name = None;
+                                    }
+                                    library.selected = None;
+                                    format!("Deleted {name}")
+                                }
+                                Err(error) => format!("Couldn't delete {name}: {error}"),
+                            });
+                        }
+                    }
+                });
+                if ui.button("Refresh").clicked() {
+                    library.refresh();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut library.save_name);
+                let name = library.save_name.trim().to_string();
+                let valid = preset::PresetLibrary::is_valid_name(&name);
+                if ui.add_enabled(valid, egui::Button::new("Save")).clicked() {
+                    library.status = Some(match library.save(&name, &current) {
+                        Ok(()) => {
+                            active_preset.set_saved(&asset_server, &mut presets, &name, current);
+                            library.selected = Some(name.clone());
+                            format!("Saved {name}")
+                        }
+                        Err(error) => format!("Couldn't save {name}: {error}"),
+                    });
+                }
+            });
+
+            if let Some(status) = &library.status {
+                ui.label(status);
+            }
+        });
+
         egui::Grid::new("settings")
             .num_columns(2)
             .spacing([40.0, 4.0])
@@ -243,17 +742,76 @@ fn ui_system(
             .show(ui, |ui| {
                 ui.label("Method");
                 ui.horizontal(|ui| {
-                    ui.radio_value(
-                        &mut ui_state.wave_method,
-                        WaveMethod::SumOfSines,
-                        "SumSines",
-                    );
-                    ui.radio_value(&mut ui_state.wave_method, WaveMethod::Fbm, "FBM");
-                    ui.radio_value(&mut ui_state.wave_method, WaveMethod::Fft, "FFT");
+                    for method in methods.iter() {
+                        ui.radio_value(
+                            &mut ui_state.wave_method,
+                            WaveMethod::named(method.name),
+                            method.label,
+                        );
+                    }
                 });
                 ui.end_row();
+
+                ui.label("Method Fade");
+                ui.add(
+                    egui::Slider::new(&mut transition_settings.duration, 0.0..=5.0)
+                        .step_by(0.1)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+
+                ui.label("Fade Dither");
+                ui.checkbox(&mut transition_settings.dither, "Dither Between Methods");
+                ui.end_row();
+
+                ui.label("Transform Test");
+                ui.checkbox(&mut ui_state.transform_test_scene, "Rotated & Scaled Water");
+                ui.end_row();
             });
 
+        // Weather
+        egui::CollapsingHeader::new("Weather").show(ui, |ui| {
+            egui::Grid::new("weather")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Wind Speed");
+                    ui.add(
+                        egui::Slider::new(&mut weather.wind_speed, 0.0..=40.0)
+                            .step_by(0.5)
+                            .suffix(" m/s"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Wind Direction");
+                    ui.add(
+                        egui::Slider::new(&mut weather.wind_direction, 0.0..=360.0)
+                            .step_by(1.0)
+                            .suffix("°"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Gustiness");
+                    ui.add(egui::Slider::new(&mut weather.gustiness, 0.0..=1.0));
+                    ui.end_row();
+
+                    ui.label("Sea State");
+                    ui.add(egui::Slider::new(&mut weather.sea_state, 0.0..=9.0).step_by(0.1));
+                    ui.end_row();
+
+                    ui.label("");
+                    if ui.button("Reset Weather").clicked() {
+                        *weather = weather::Weather::default();
+                    }
+                    ui.end_row();
+                });
+            ui.label(
+                "FFT water isn't simulated yet, so it stays flat whatever the weather. Baked \
+                 water keeps the weather it was baked with.",
+            );
+        });
+
         // Shading
         egui::CollapsingHeader::new("Shading").show(ui, |ui| {
             egui::Grid::new("shading")
@@ -311,229 +869,95 @@ fn ui_system(
                     ui.label("Tip Color");
                     ui.color_edit_button_srgba(&mut ui_state.colors.tip);
                     ui.end_row();
-                });
-        });
-
-        // FBM
-        if *wave_method == WaveMethod::Fbm {
-            egui::CollapsingHeader::new("FBM Vertex Shader").show(ui, |ui| {
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_seed, 0.0..=300.0)
-                        .step_by(1.0)
-                        .text("Vertex Seed"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_seed_iter, 0.0..=2000.0)
-                        .step_by(10.0)
-                        .text("Vertex Seed Iterator"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_frequency, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Frequency"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_frequency_mult, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Frequency Mult"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_amplitude, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Amplitude"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_amplitude_mult, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Amplitude Mult"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_max_peak, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Max Peak"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_peak_offset, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Peak Offset"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_initial_speed, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Initial Speed"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_speed_ramp, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Speed Ramp"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_drag, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Drag"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.vertex_height, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Vertex Height"),
-                );
-            });
-            egui::CollapsingHeader::new("FBM Fragment Shader").show(ui, |ui| {
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_seed, 0.0..=300.0)
-                        .step_by(1.0)
-                        .text("Fragment Seed"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_seed_iter, 0.0..=2000.0)
-                        .step_by(10.0)
-                        .text("Fragment Seed Iterator"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_frequency, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Frequency"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_frequency_mult, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Frequency Mult"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_amplitude, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Amplitude"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_amplitude_mult, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Amplitude Mult"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_max_peak, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Max Peak"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_peak_offset, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Peak Offset"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_initial_speed, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Initial Speed"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_speed_ramp, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Speed Ramp"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_drag, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Drag"),
-                );
-                ui.add(
-                    egui::Slider::new(&mut ui_state.fbm_config.fragment_height, 0.0..=2.0)
-                        .step_by(0.1)
-                        .text("Fragment Height"),
-                );
-            });
-        }
 
-        if *wave_method == WaveMethod::SumOfSines {
-            // TODO: Add wave settings
-
-            egui::Grid::new("sum_of_sines")
-                .num_columns(2)
-                .spacing([40.0, 4.0])
-                .striped(true)
-                .show(ui, |ui| {
-                    ui.label("Wave Type");
+                    ui.label("Normals");
                     ui.horizontal(|ui| {
-                        ui.radio_value(&mut ui_state.wave_type, sum_water::WaveType::Sine, "Sine");
                         ui.radio_value(
-                            &mut ui_state.wave_type,
-                            sum_water::WaveType::SteepSine,
-                            "Steep Sine",
+                            &mut ui_state.shading.normal_mode,
+                            common::NormalMode::PerPixel,
+                            "Per Pixel",
+                        );
+                        ui.radio_value(
+                            &mut ui_state.shading.normal_mode,
+                            common::NormalMode::VertexWithDetail,
+                            "Vertex + Detail",
                         );
                     });
                     ui.end_row();
 
-                    ui.label("");
-                    let button = ui.button("Regenerate Waves");
-                    if button.clicked() {
-                        for material in sum_materials.iter_mut() {
-                            material.1.randomize(global_rng.as_mut());
-                        }
+                    if ui_state.shading.normal_mode == common::NormalMode::VertexWithDetail {
+                        ui.label("Detail Strength");
+                        ui.add(
+                            egui::Slider::new(&mut ui_state.shading.detail_strength, 0.0..=1.0)
+                                .step_by(0.01),
+                        );
+                        ui.end_row();
+
+                        ui.label("Detail Frequency");
+                        ui.add(
+                            egui::Slider::new(&mut ui_state.shading.detail_frequency, 0.0..=20.0)
+                                .step_by(0.1),
+                        );
+                        ui.end_row();
                     }
-                    ui.end_row();
                 });
-        }
+        });
     });
 }
 
+/// Fill the settings window from the newly selected water body.
+#[allow(clippy::type_complexity)]
+fn load_selected_water(
+    selected: Res<SelectedWater>,
+    bodies: Query<
+        (
+            &WaveMethod,
+            &sum_water::WaveType,
+            &common::Shading,
+            &fbm_water::FbmWaterConfig,
+        ),
+        With<WaterBody>,
+    >,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<history::UiHistory>,
+) {
+    let Some(Ok((wave_method, wave_type, shading, fbm_config))) =
+        selected.0.map(|entity| bodies.get(entity))
+    else {
+        return;
+    };
+    ui_state.wave_method = wave_method.clone();
+    ui_state.wave_type = *wave_type;
+    ui_state.shading = shading.clone();
+    ui_state.colors = Colors::from(shading);
+    ui_state.fbm_config = fbm_config.clone();
+    // Undoing into a different water body's settings would be confusing
+    history.reset(&ui_state);
+}
+
+/// Write the settings window back onto the selected water body.
+#[allow(clippy::type_complexity)]
 fn ui_state_update(
-    mut commands: Commands,
     ui_state: Res<UiState>,
-    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
-    mut fbm_materials: ResMut<Assets<fbm_water::FbmWaterMaterial>>,
-    wave_method: Res<WaveMethod>,
-    wave_type: Res<sum_water::WaveType>,
+    selected: Res<SelectedWater>,
+    mut bodies: Query<
+        (
+            &mut WaveMethod,
+            &mut sum_water::WaveType,
+            &mut common::Shading,
+            &mut fbm_water::FbmWaterConfig,
+        ),
+        With<WaterBody>,
+    >,
 ) {
-    if *wave_method != ui_state.wave_method {
-        commands.insert_resource(ui_state.wave_method);
-    }
-    if *wave_type != ui_state.wave_type {
-        commands.insert_resource(ui_state.wave_type);
-    }
-
-    for material in sum_materials.iter_mut() {
-        material.1.shading = ui_state.shading.clone();
-        material.1.shading.ambient = Color::rgb_u8(
-            ui_state.colors.ambient.r(),
-            ui_state.colors.ambient.g(),
-            ui_state.colors.ambient.b(),
-        );
-        material.1.shading.diffuse_reflectance = Color::rgb_u8(
-            ui_state.colors.diffuse.r(),
-            ui_state.colors.diffuse.g(),
-            ui_state.colors.diffuse.b(),
-        );
-        material.1.shading.specular_reflectance = Color::rgb_u8(
-            ui_state.colors.specular.r(),
-            ui_state.colors.specular.g(),
-            ui_state.colors.specular.b(),
-        );
-        material.1.shading.tip_color = Color::rgb_u8(
-            ui_state.colors.tip.r(),
-            ui_state.colors.tip.g(),
-            ui_state.colors.tip.b(),
-        );
-    }
-    for material in fbm_materials.iter_mut() {
-        material.1.shading = ui_state.shading.clone();
-        material.1.shading.ambient = Color::rgb_u8(
-            ui_state.colors.ambient.r(),
-            ui_state.colors.ambient.g(),
-            ui_state.colors.ambient.b(),
-        );
-        material.1.shading.diffuse_reflectance = Color::rgb_u8(
-            ui_state.colors.diffuse.r(),
-            ui_state.colors.diffuse.g(),
-            ui_state.colors.diffuse.b(),
-        );
-        material.1.shading.specular_reflectance = Color::rgb_u8(
-            ui_state.colors.specular.r(),
-            ui_state.colors.specular.g(),
-            ui_state.colors.specular.b(),
-        );
-        material.1.shading.tip_color = Color::rgb_u8(
-            ui_state.colors.tip.r(),
-            ui_state.colors.tip.g(),
-            ui_state.colors.tip.b(),
-        );
-        material.1.fbm_config = ui_state.fbm_config.clone();
-    }
+    let Some(Ok((mut wave_method, mut wave_type, mut shading, mut fbm_config))) =
+        selected.0.map(|entity| bodies.get_mut(entity))
+    else {
+        return;
+    };
+    // Only touch what differs, so unchanged settings don't trigger material updates
+    wave_method.set_if_neq(ui_state.wave_method.clone());
+    wave_type.set_if_neq(ui_state.wave_type);
+    shading.set_if_neq(ui_state.shading());
+    fbm_config.set_if_neq(ui_state.fbm_config.clone());
 }
@@ -0,0 +1,744 @@
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    render::{primitives::Aabb, render_resource::AsBindGroup, view::VisibilitySystems},
+    transform::TransformSystem,
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, hash::Hash, sync::Arc};
+
+use crate::{
+    bake::bake_selected_water,
+    common,
+    debug::WaterDebug,
+    export::export_selected_water,
+    fbm_water, headless, load_selected_water,
+    performance::WaterStats,
+    preset::WaterPreset,
+    quality::{QualitySettings, WaterQuality},
+    sequence, ui_state_update,
+    water_time::{loop_error, LoopCheck, LoopCheckResult, WaterTime, WaterTimeOffset},
+    weather::Weather,
+    SelectedWater, UiState, Water, WaterBody, WaterMesh, WaterTile,
+};
+
+/// A way of simulating and rendering water, implemented by its material.
+///
+/// Methods are added with [`WaterMethodsPlugin::with_method`]. Every water body then gets its own
+/// material for each method, and its [`WaveMethod`] picks which one its tiles render with.
+pub trait WaterMethod: Material + PartialEq + Reflect + FromReflect + GetTypeRegistration {
+    /// Identifies the method in `WaveMethod` components and presets.
+    const NAME: &'static str;
+    /// Shown in the settings window.
+    const LABEL: &'static str;
+    /// Whether `height_at` follows the surface the shaders draw. Methods whose heights only live
+    /// on the GPU report a flat surface instead.
+    const CPU_HEIGHT: bool = true;
+
+    /// The material for a newly spawned water body.
+    fn new_material(rng: &mut GlobalRng) -> Self;
+
+    /// The `WaterTime` clock, which the material works its wave phases out from.
+    fn set_clock(&mut self, clock: common::WaveClock);
+
+    fn apply_shading(&mut self, shading: &common::Shading);
+
+    /// Map the shared weather onto the method's own settings.
+    fn apply_weather(&mut self, weather: &Weather);
+
+    /// Scale the material's waves and limit which pixels it draws while cross-fading to or from
+    /// another method.
+    fn set_fade(&mut self, fade: common::Fade);
+
+    /// Switch the shader to a debug view or wireframe overlay.
+    fn set_debug_draw(&mut self, debug_draw: common::DebugDraw);
+
+    /// Cap the method's detail and optional effects at a `WaterQuality` tier's settings.
+    fn set_quality(&mut self, quality: QualitySettings);
+
+    /// The (min, max) offset any vertex can be displaced by.
+    fn displacement_bounds(&self) -> (Vec3, Vec3);
+
+    /// Waves the shaders currently evaluate per vertex and per pixel.
+    fn wave_counts(&self) -> common::WaveCounts;
+
+    /// Height the vertex shader lifts the surface by at a position along it, in the surface frame.
+    fn height_at(&self, surface_position: Vec2) -> f32;
+
+    /// Whether the material has anything to draw, e.g. a flipbook has been loaded to play back.
+    fn has_content(&self) -> bool {
+        true
+    }
+
+    /// Take on the method's own settings from a preset. Most of them reach the material through its
+    /// water body's components in the app, so there this is only needed for what the material alone
+    /// keeps, such as a sequence's waves.
+    fn apply_preset(&mut self, _preset: &WaterPreset) {}
+
+    /// Draw the method's own settings. Anything that should be undoable or saved in presets is
+    /// edited through `ui_state`.
+    fn draw_ui(&mut self, _ui: &mut egui::Ui, _ui_state: &mut UiState, _rng: &mut GlobalRng) {}
+
+    /// Add any systems the method needs on top of the shared ones.
+    fn build(_app: &mut App) {}
+}
+
+/// The `WaterMethod` a water body uses, by its `WaterMethod::NAME`.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(transparent)]
+pub struct WaveMethod(Cow<'static, str>);
+
+impl WaveMethod {
+    pub fn of<M: WaterMethod>() -> Self {
+        WaveMethod(Cow::Borrowed(M::NAME))
+    }
+
+    pub fn is<M: WaterMethod>(&self) -> bool {
+        self.0 == M::NAME
+    }
+
+    pub fn named(name: &'static str) -> Self {
+        WaveMethod(Cow::Borrowed(name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for WaveMethod {
+    fn default() -> Self {
+        WaveMethod::of::<fbm_water::FbmWaterMaterial>()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MethodInfo {
+    pub name: &'static str,
+    pub label: &'static str,
+    /// Does what the command line asks for with a fresh material of the method, without the app.
+    pub run_headless: headless::RunHeadless,
+    /// Adds the method's material and systems to the app.
+    add: fn(&mut App),
+}
+
+impl MethodInfo {
+    fn of<M: WaterMethod>() -> Self
+    where
+        <M as AsBindGroup>::Data: PartialEq + Eq + Hash + Clone,
+    {
+        MethodInfo {
+            name: M::NAME,
+            label: M::LABEL,
+            run_headless: headless::run::<M>,
+            add: add_method::<M>,
+        }
+    }
+}
+
+/// Every registered method, in the order they were added.
+#[derive(Resource, Debug, Default)]
+pub struct WaterMethods(Vec<MethodInfo>);
+
+impl WaterMethods {
+    pub fn iter(&self) -> impl Iterator<Item = &MethodInfo> {
+        self.0.iter()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&MethodInfo> {
+        self.0.iter().find(|method| method.name == name)
+    }
+
+    /// The names of every method, for messages about an unknown one.
+    pub fn names(&self) -> String {
+        let names: Vec<_> = self.0.iter().map(|method| method.name).collect();
+        names.join(", ")
+    }
+}
+
+/// A water body's material for one method.
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct MethodMaterial<M: WaterMethod>(pub Handle<M>);
+
+impl<M: WaterMethod> Default for MethodMaterial<M> {
+    fn default() -> Self {
+        MethodMaterial(Handle::default())
+    }
+}
+
+/// How long switching a water body to another method takes.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct MethodTransitionSettings {
+    /// Length of the cross-fade in seconds, or zero to switch instantly.
+    pub duration: f32,
+    /// Draw both methods through the whole cross-fade, dithered between them, rather than
+    /// flattening one before raising the other. See [`common::Fade`].
+    pub dither: bool,
+}
+
+impl Default for MethodTransitionSettings {
+    fn default() -> Self {
+        MethodTransitionSettings {
+            duration: 1.0,
+            dither: false,
+        }
+    }
+}
+
+/// A water body cross-fading from its previous method to its current [`WaveMethod`]. Its tiles
+/// render with both materials until the transition is done.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct MethodTransition {
+    pub from: WaveMethod,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl MethodTransition {
+    /// How far along the fade is, eased so it starts and ends gently.
+    pub fn progress(&self) -> f32 {
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Whether a water body's tiles render with method `M`: it uses it, or is fading out of it.
+pub fn shows_method<M: WaterMethod>(
+    wave_method: &WaveMethod,
+    transition: Option<&MethodTransition>,
+) -> bool {
+    wave_method.is::<M>() || transition.is_some_and(|transition| transition.from.is::<M>())
+}
+
+/// Edit a material through a copy, and only write it back if that changed it. Every `get_mut`
+/// has the material prepared for the GPU again and marks `materials` as changed, so this is
+/// `set_if_neq` for assets.
+fn edit_material<M: WaterMethod>(
+    materials: &mut ResMut<Assets<M>>,
+    handle: &Handle<M>,
+    edit: impl FnOnce(&mut M),
+) {
+    let Some(material) = materials.get(handle) else {
+        return;
+    };
+    let mut edited = material.clone();
+    edit(&mut edited);
+    if edited != *material {
+        if let Some(material) = materials.get_mut(handle) {
+            *material = edited;
+        }
+    }
+}
+
+/// The method a water body was last switched to, which a new transition starts from.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+struct ShownMethod(WaveMethod);
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WaterMethodSet {
+    /// Start and advance cross-fades between methods.
+    Transition,
+    /// Keep each method's materials and tile handles up to date.
+    Update,
+    /// Fit each tile's bounding box around every material it renders with.
+    Bounds,
+}
+
+/// CPU copy of a water body's current surface, for gameplay queries such as buoyancy.
+#[derive(Component, Clone)]
+pub struct WaterSurface {
+    height: Arc<dyn Fn(Vec2) -> f32 + Send + Sync>,
+    /// World space frame of the undisplaced surface, matching `surface_frame` in the shaders.
+    frame: Mat3,
+    origin: Vec3,
+}
+
+impl WaterSurface {
+    pub fn normal(&self) -> Vec3 {
+        self.frame.y_axis
+    }
+
+    /// The point on the displaced surface directly above or below `world_position`.
+    pub fn surface_point(&self, world_position: Vec3) -> Vec3 {
+        let surface_position = self.frame.transpose() * world_position;
+        let height_above_plane = self.normal().dot(world_position - self.origin);
+        let base = world_position - self.normal() * height_above_plane;
+        base + self.normal() * (self.height)(Vec2::new(surface_position.x, surface_position.z))
+    }
+
+    /// How far `world_position` is below the surface, or negative if it's above it.
+    pub fn depth(&self, world_position: Vec3) -> f32 {
+        self.normal()
+            .dot(self.surface_point(world_position) - world_position)
+    }
+}
+
+/// The surface frame of an entity: its x, up and z directions with scale and shear removed.
+fn surface_frame(transform: &GlobalTransform) -> Mat3 {
+    let matrix = transform.affine().matrix3;
+    let normal = (matrix.inverse().transpose() * Vec3::Y).normalize();
+    let tangent = matrix * Vec3::X;
+    let bitangent = tangent.cross(normal).normalize();
+    Mat3::from_cols(normal.cross(bitangent), normal, bitangent)
+}
+
+/// Registers water methods and the systems shared by all of them.
+#[derive(Default)]
+pub struct WaterMethodsPlugin {
+    methods: Vec<MethodInfo>,
+}
+
+impl WaterMethodsPlugin {
+    pub fn with_method<M: WaterMethod>(mut self) -> Self
+    where
+        <M as AsBindGroup>::Data: PartialEq + Eq + Hash + Clone,
+    {
+        self.methods.push(MethodInfo::of::<M>());
+        self
+    }
+
+    /// The methods that have been added, for use before there's an app to add them to.
+    pub fn methods(&self) -> WaterMethods {
+        WaterMethods(self.methods.clone())
+    }
+}
+
+impl Plugin for WaterMethodsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.methods())
+            .init_resource::<MethodTransitionSettings>()
+            .init_resource::<Weather>()
+            .register_type::<MethodTransitionSettings>()
+            .register_type::<Weather>()
+            .register_type::<MethodTransition>()
+            .register_type::<ShownMethod>()
+            .configure_sets(
+                Update,
+                (WaterMethodSet::Transition, WaterMethodSet::Update)
+                    .chain()
+                    .after(ui_state_update),
+            )
+            .configure_set(
+                PostUpdate,
+                WaterMethodSet::Bounds
+                    .after(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::CheckVisibility),
+            )
+            .add_systems(
+                Update,
+                (start_transitions, advance_transitions, apply_deferred)
+                    .chain()
+                    .in_set(WaterMethodSet::Transition),
+            )
+            .add_systems(
+                PostUpdate,
+                reset_water_bounds.before(WaterMethodSet::Bounds),
+            );
+        for method in &self.methods {
+            (method.add)(app);
+        }
+    }
+}
+
+fn add_method<M: WaterMethod>(app: &mut App)
+where
+    <M as AsBindGroup>::Data: PartialEq + Eq + Hash + Clone,
+{
+    app.add_plugins(MaterialPlugin::<M>::default())
+        .register_asset_reflect::<M>()
+        .register_type::<MethodMaterial<M>>()
+        .add_systems(
+            Update,
+            (
+                (
+                    add_method_material::<M>,
+                    switch_method::<M>,
+                    update_method_time::<M>,
+                    update_method_fade::<M>,
+                    update_method_debug_draw::<M>,
+                    update_method_quality::<M>,
+                    apply_method_shading::<M>,
+                    apply_method_weather::<M>,
+                )
+                    .in_set(WaterMethodSet::Update),
+                (
+                    check_method_loop::<M>,
+                    bake_selected_water::<M>,
+                    export_selected_water::<M>,
+                    report_wave_counts::<M>,
+                    report_method_content::<M>,
+                )
+                    .after(WaterMethodSet::Update),
+                method_ui::<M>
+                    .after(load_selected_water)
+                    .before(ui_state_update),
+                sequence::apply_sequence_material::<M>
+                    .after(sequence::apply_sequences)
+                    .before(ui_state_update),
+            ),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                update_method_bounds::<M>.in_set(WaterMethodSet::Bounds),
+                update_water_surface::<M>.after(TransformSystem::TransformPropagate),
+            ),
+        );
+    M::build(app);
+}
+
+fn add_method_material<M: WaterMethod>(
+    mut commands: Commands,
+    bodies: Query<Entity, (With<WaterBody>, Without<MethodMaterial<M>>)>,
+    mut materials: ResMut<Assets<M>>,
+    mut global_rng: ResMut<GlobalRng>,
+) {
+    for entity in bodies.iter() {
+        let material = materials.add(M::new_material(global_rng.as_mut()));
+        commands.entity(entity).insert(MethodMaterial(material));
+    }
+}
+
+/// Cross-fade water bodies whose method has just changed, or switch instantly if fading is off.
+fn start_transitions(
+    mut commands: Commands,
+    bodies: Query<(Entity, &WaveMethod, Option<&ShownMethod>), Changed<WaveMethod>>,
+    settings: Res<MethodTransitionSettings>,
+) {
+    for (entity, wave_method, shown) in bodies.iter() {
+        let mut body = commands.entity(entity);
+        body.insert(ShownMethod(wave_method.clone()));
+        match shown {
+            Some(ShownMethod(from)) if from != wave_method && settings.duration > 0.0 => {
+                body.insert(MethodTransition {
+                    from: from.clone(),
+                    elapsed: 0.0,
+                    duration: settings.duration,
+                });
+            }
+            _ => {
+                body.remove::<MethodTransition>();
+            }
+        }
+    }
+}
+
+fn advance_transitions(
+    mut commands: Commands,
+    mut bodies: Query<(Entity, &mut MethodTransition)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transition) in bodies.iter_mut() {
+        // Finished transitions are kept for a frame so the outgoing material is fully faded
+        // before its handle is removed
+        if transition.elapsed >= transition.duration {
+            commands.entity(entity).remove::<MethodTransition>();
+        } else {
+            transition.elapsed += time.delta_seconds();
+        }
+    }
+}
+
+/// Give a water body's tiles this method's material while the body uses it or is fading out of
+/// it, and take it away otherwise.
+fn switch_method<M: WaterMethod>(
+    mut commands: Commands,
+    bodies: Query<(
+        &WaveMethod,
+        Option<&MethodTransition>,
+        &MethodMaterial<M>,
+        &Children,
+    )>,
+    water_query: Query<Option<&Handle<M>>, With<Water>>,
+) {
+    for (wave_method, transition, material, children) in bodies.iter() {
+        let shown = shows_method::<M>(wave_method, transition);
+        for &entity in children.iter() {
+            let Ok(handle) = water_query.get(entity) else {
+                continue;
+            };
+            match (shown, handle) {
+                (true, None) => {
+                    commands.entity(entity).insert(material.0.clone());
+                }
+                (false, Some(_)) => {
+                    commands.entity(entity).remove::<Handle<M>>();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Keep the clock of each material on screen up to date. Like the fade, debug draw and quality
+/// updates below, this leaves hidden materials alone. They catch up in the frame their body shows
+/// them again.
+#[allow(clippy::type_complexity)]
+fn update_method_time<M: WaterMethod>(
+    bodies: Query<(
+        &WaveMethod,
+        Option<&MethodTransition>,
+        &MethodMaterial<M>,
+        Option<&WaterTimeOffset>,
+    )>,
+    mut materials: ResMut<Assets<M>>,
+    water_time: Res<WaterTime>,
+) {
+    for (wave_method, transition, material, offset) in bodies.iter() {
+        if !shows_method::<M>(wave_method, transition) {
+            continue;
+        }
+        let clock = water_time.clock(offset.map_or(0.0, |offset| offset.0));
+        edit_material(&mut materials, &material.0, |material| {
+            material.set_clock(clock);
+        });
+    }
+}
+
+fn update_method_fade<M: WaterMethod>(
+    bodies: Query<(&WaveMethod, Option<&MethodTransition>, &MethodMaterial<M>)>,
+    mut materials: ResMut<Assets<M>>,
+    settings: Res<MethodTransitionSettings>,
+) {
+    for (wave_method, transition, material) in bodies.iter() {
+        if !shows_method::<M>(wave_method, transition) {
+            continue;
+        }
+        let fade = match transition {
+            Some(transition) if wave_method.is::<M>() => {
+                common::Fade::incoming(transition.progress(), settings.dither)
+            }
+            Some(transition) if transition.from.is::<M>() => {
+                common::Fade::outgoing(transition.progress(), settings.dither)
+            }
+            _ => common::Fade::VISIBLE,
+        };
+        edit_material(&mut materials, &material.0, |material| {
+            material.set_fade(fade);
+        });
+    }
+}
+
+fn update_method_debug_draw<M: WaterMethod>(
+    bodies: Query<(&WaveMethod, Option<&MethodTransition>, &MethodMaterial<M>)>,
+    mut materials: ResMut<Assets<M>>,
+    debug: Res<WaterDebug>,
+    water_mesh: Option<Res<WaterMesh>>,
+) {
+    let debug_draw = common::DebugDraw {
+        view: debug.view,
+        wireframe_quads: match &water_mesh {
+            Some(water_mesh) if debug.wireframe => water_mesh.quads_per_side,
+            _ => 0,
+        },
+    };
+    for (wave_method, transition, material) in bodies.iter() {
+        if shows_method::<M>(wave_method, transition) {
+            edit_material(&mut materials, &material.0, |material| {
+                material.set_debug_draw(debug_draw);
+            });
+        }
+    }
+}
+
+fn update_method_quality<M: WaterMethod>(
+    bodies: Query<(&WaveMethod, Option<&MethodTransition>, &MethodMaterial<M>)>,
+    mut materials: ResMut<Assets<M>>,
+    quality: Res<WaterQuality>,
+) {
+    let settings = quality.settings();
+    for (wave_method, transition, material) in bodies.iter() {
+        if shows_method::<M>(wave_method, transition) {
+            edit_material(&mut materials, &material.0, |material| {
+                material.set_quality(settings);
+            });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_method_shading<M: WaterMethod>(
+    bodies: Query<
+        (&MethodMaterial<M>, &common::Shading),
+        Or<(Changed<common::Shading>, Added<MethodMaterial<M>>)>,
+    >,
+    mut materials: ResMut<Assets<M>>,
+) {
+    for (material, shading) in bodies.iter() {
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.apply_shading(shading);
+        }
+    }
+}
+
+fn apply_method_weather<M: WaterMethod>(
+    bodies: Query<Ref<MethodMaterial<M>>>,
+    mut materials: ResMut<Assets<M>>,
+    weather: Res<Weather>,
+) {
+    for material in bodies.iter() {
+        if !weather.is_changed() && !material.is_added() {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.apply_weather(&weather);
+        }
+    }
+}
+
+/// Answer the time window's loop check if the selected water body uses this method.
+fn check_method_loop<M: WaterMethod>(
+    mut loop_check: ResMut<LoopCheck>,
+    water_time: Res<WaterTime>,
+    selected: Res<SelectedWater>,
+    bodies: Query<(&WaveMethod, &MethodMaterial<M>)>,
+    materials: Res<Assets<M>>,
+) {
+    if !loop_check.requested {
+        return;
+    }
+    let Some((wave_method, material)) = selected.0.and_then(|entity| bodies.get(entity).ok())
+    else {
+        return;
+    };
+    let (true, Some(material), Some(period)) = (
+        wave_method.is::<M>(),
+        materials.get(&material.0),
+        water_time.loop_period,
+    ) else {
+        return;
+    };
+    loop_check.result = Some(match loop_error(material, Some(period), period) {
+        Some(error) => LoopCheckResult::Error(error),
+        None => LoopCheckResult::NoCpuHeight,
+    });
+    loop_check.requested = false;
+}
+
+/// Draw the method's settings for the selected water body, if it uses this method.
+fn method_ui<M: WaterMethod>(
+    mut contexts: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    selected: Res<SelectedWater>,
+    bodies: Query<&MethodMaterial<M>>,
+    mut materials: ResMut<Assets<M>>,
+    mut global_rng: ResMut<GlobalRng>,
+) {
+    if !ui_state.wave_method.is::<M>() {
+        return;
+    }
+    let Some(material) = selected.0.and_then(|entity| bodies.get(entity).ok()) else {
+        return;
+    };
+    edit_material(&mut materials, &material.0, |material| {
+        egui::Window::new(M::LABEL).show(contexts.ctx_mut(), |ui| {
+            material.draw_ui(ui, &mut ui_state, global_rng.as_mut());
+        });
+    });
+}
+
+/// Show how many waves the selected water body's material evaluates, if it's using this method.
+fn report_wave_counts<M: WaterMethod>(
+    selected: Res<SelectedWater>,
+    bodies: Query<(&WaveMethod, &MethodMaterial<M>)>,
+    materials: Res<Assets<M>>,
+    mut stats: ResMut<WaterStats>,
+) {
+    let Some((wave_method, material)) = selected.0.and_then(|entity| bodies.get(entity).ok())
+    else {
+        return;
+    };
+    if let (true, Some(material)) = (wave_method.is::<M>(), materials.get(&material.0)) {
+        stats.wave_counts = material.wave_counts();
+    }
+}
+
+/// Note whether the selected water body's material of this method has anything to draw.
+fn report_method_content<M: WaterMethod>(
+    selected: Res<SelectedWater>,
+    bodies: Query<&MethodMaterial<M>>,
+    materials: Res<Assets<M>>,
+    mut stats: ResMut<WaterStats>,
+) {
+    let Some(material) = selected
+        .0
+        .and_then(|entity| bodies.get(entity).ok())
+        .and_then(|material| materials.get(&material.0))
+    else {
+        return;
+    };
+    stats.method_content.insert(M::NAME, material.has_content());
+}
+
+/// Shrink every tile's bounding box back to the flat surface, for each method's materials to grow.
+fn reset_water_bounds(mut water_query: Query<(&WaterTile, &mut Aabb), With<Water>>) {
+    for (tile, mut aabb) in water_query.iter_mut() {
+        *aabb = Aabb::from_min_max(tile.flat_min, tile.flat_max);
+    }
+}
+
+/// Keep each tile's bounding box in sync with its materials, so frustum culling never drops
+/// displaced geometry that is actually on screen.
+fn update_method_bounds<M: WaterMethod>(
+    materials: Res<Assets<M>>,
+    mut water_query: Query<(&WaterTile, &GlobalTransform, &mut Aabb, &Handle<M>), With<Water>>,
+) {
+    for (tile, global_transform, mut aabb, handle) in water_query.iter_mut() {
+        let Some(material) = materials.get(handle) else {
+            continue;
+        };
+        let scale = global_transform.compute_transform().scale;
+        let displaced = tile.displaced_aabb(material.displacement_bounds(), scale);
+        *aabb = Aabb::from_min_max(
+            aabb.min().min(displaced.min()).into(),
+            aabb.max().max(displaced.max()).into(),
+        );
+    }
+}
+
+/// Give a water body using this method a `WaterSurface`, and refresh it when the body moves,
+/// switches to this method or one of its materials changes.
+#[allow(clippy::type_complexity)]
+fn update_water_surface<M: WaterMethod>(
+    mut commands: Commands,
+    mut bodies: Query<(
+        Entity,
+        Ref<WaveMethod>,
+        &MethodMaterial<M>,
+        Ref<GlobalTransform>,
+        Option<&mut WaterSurface>,
+    )>,
+    materials: Res<Assets<M>>,
+) {
+    for (entity, wave_method, material, global_transform, surface) in bodies.iter_mut() {
+        let stale = surface.is_none()
+            || wave_method.is_changed()
+            || global_transform.is_changed()
+            || materials.is_changed();
+        if !wave_method.is::<M>() || !stale {
+            continue;
+        }
+        let Some(material) = materials.get(&material.0) else {
+            continue;
+        };
+        let material = material.clone();
+        let updated = WaterSurface {
+            height: Arc::new(move |surface_position| material.height_at(surface_position)),
+            frame: surface_frame(&global_transform),
+            origin: global_transform.translation(),
+        };
+        match surface {
+            Some(mut surface) => *surface = updated,
+            None => {
+                commands.entity(entity).insert(updated);
+            }
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use bevy::{app::AppExit, asset::FileAssetIo, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    common::{NormalMode, WaveCounts},
+    gpu_timing::GpuPassTime,
+    history::UiHistory,
+    load_selected_water,
+    method::{MethodInfo, MethodTransitionSettings, WaterMethods, WaveMethod},
+    quality::WaterQuality,
+    ui_state_update, UiState, Water, WaterMesh,
+};
+
+const BENCHMARK_DIRECTORY: &str = "benchmarks";
+/// Frames the HUD averages its frame time over.
+const FRAME_HISTORY: usize = 120;
+/// Seconds each benchmark step runs once its method transition is over before it's measured, long
+/// enough for any shaders to finish compiling.
+const BENCHMARK_WARMUP: f32 = 2.0;
+/// Seconds each benchmark step is measured for.
+const BENCHMARK_DURATION: f32 = 5.0;
+
+/// What the water is costing this frame, as shown in the performance overlay.
+#[derive(Resource, Debug, Default)]
+pub struct WaterStats {
+    /// Most recent frame times in milliseconds, oldest first.
+    frame_times: VecDeque<f32>,
+    /// Vertices in a single water tile.
+    pub tile_vertices: usize,
+    /// Water tiles that passed culling last frame.
+    pub visible_tiles: usize,
+    /// Waves the selected water body's current method evaluates.
+    pub wave_counts: WaveCounts,
+    /// Whether the selected water body's material of each method has anything to draw, by
+    /// method name. The benchmark skips those that don't, such as a flipbook player with no
+    /// flipbook.
+    pub method_content: HashMap<&'static str, bool>,
+}
+
+impl WaterStats {
+    pub fn vertices_drawn(&self) -> usize {
+        self.tile_vertices * self.visible_tiles
+    }
+
+    /// Mean, shortest and longest recent frame times in milliseconds.
+    pub fn frame_time(&self) -> Option<(f32, f32, f32)> {
+        let count = self.frame_times.len();
+        (count > 0).then(|| {
+            let (sum, min, max) = self
+                .frame_times
+                .iter()
+                .fold((0.0, f32::INFINITY, 0.0_f32), |(sum, min, max), &time| {
+                    (sum + time, min.min(time), max.max(time))
+                });
+            (sum / count as f32, min, max)
+        })
+    }
+}
+
+/// A combination of settings the benchmark measures.
+#[derive(Debug, Clone, PartialEq)]
+struct BenchmarkStep {
+    method: WaveMethod,
+    method_label: &'static str,
+    quality: WaterQuality,
+    normal_mode: NormalMode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BenchmarkResult {
+    step: BenchmarkStep,
+    frames: usize,
+    mean_frame_time: f32,
+    p95_frame_time: f32,
+    max_frame_time: f32,
+    mean_gpu_time: Option<f32>,
+    vertices_drawn: usize,
+    wave_counts: WaveCounts,
+}
+
+#[derive(Debug)]
+struct BenchmarkRun {
+    steps: Vec<BenchmarkStep>,
+    current: usize,
+    /// Seconds into the current step, including its warmup.
+    elapsed: f32,
+    frame_times: Vec<f32>,
+    gpu_times: Vec<f32>,
+    results: Vec<BenchmarkResult>,
+    /// Methods left out for having nothing to draw.
+    skipped: Vec<&'static str>,
+    /// The settings from before the benchmark, to go back to afterwards.
+    restore: UiState,
+    restore_quality: WaterQuality,
+}
+
+/// Cycles the selected water body through every method, quality tier and normal mode, measures
+/// each, and writes the results to a CSV file in the `benchmarks` directory.
+#[derive(Resource, Debug, Default)]
+pub struct Benchmark {
+    run: Option<BenchmarkRun>,
+    requested: bool,
+    /// Quit once the report is written, for runs started from the command line.
+    pub exit_when_done: bool,
+    /// Outcome of the last benchmark, shown in the overlay.
+    pub status: Option<String>,
+}
+
+impl Benchmark {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+}
+
+pub struct PerformancePlugin;
+
+impl Plugin for PerformancePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterStats>()
+            .init_resource::<Benchmark>()
+            .add_systems(
+                Update,
+                (
+                    update_water_stats,
+                    (performance_ui, run_benchmark)
+                        .chain()
+                        .after(load_selected_water)
+                        .before(ui_state_update),
+                ),
+            );
+    }
+}
+
+fn update_water_stats(
+    mut stats: ResMut<WaterStats>,
+    time: Res<Time>,
+    water_mesh: Option<Res<WaterMesh>>,
+    meshes: Res<Assets<Mesh>>,
+    tiles: Query<&ComputedVisibility, With<Water>>,
+) {
+    stats.frame_times.push_back(time.delta_seconds() * 1000.0);
+    if stats.frame_times.len() > FRAME_HISTORY {
+        stats.frame_times.pop_front();
+    }
+    stats.tile_vertices = water_mesh
+        .and_then(|water_mesh| meshes.get(&water_mesh.mesh))
+        .map_or(0, |mesh| mesh.count_vertices());
+    stats.visible_tiles = tiles
+        .iter()
+        .filter(|visibility| visibility.is_visible())
+        .count();
+}
+
+fn performance_ui(
+    mut contexts: EguiContexts,
+    stats: Res<WaterStats>,
+    gpu_pass_time: Res<GpuPassTime>,
+    mut benchmark: ResMut<Benchmark>,
+) {
+    let mut requested = false;
+    egui::Window::new("Performance")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("performance")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Frame Time");
+                    match stats.frame_time() {
+                        Some((mean, min, max)) => ui.label(format!(
+                            "{mean:.2} ms ({:.0} fps), {min:.2} to {max:.2} ms",
+                            1000.0 / mean
+                        )),
+                        None => ui.label("-"),
+                    };
+                    ui.end_row();
+
+                    ui.label("GPU Opaque Pass")
+                        .on_hover_text("Everything opaque on screen, not only the water");
+                    match gpu_pass_time.milliseconds() {
+                        Some(milliseconds) => ui.label(format!("{milliseconds:.2} ms")),
+                        None if gpu_pass_time.supported() => ui.label("Waiting"),
+                        None => ui.label("No timestamp queries on this GPU"),
+                    };
+                    ui.end_row();
+
+                    ui.label("Water Vertices");
+                    ui.label(format!(
+                        "{} ({} tiles of {})",
+                        stats.vertices_drawn(),
+                        stats.visible_tiles,
+                        stats.tile_vertices
+                    ));
+                    ui.end_row();
+
+                    ui.label("Waves per Vertex");
+                    ui.label(stats.wave_counts.vertex.to_string());
+                    ui.end_row();
+
+                    ui.label("Waves per Pixel");
+                    ui.label(stats.wave_counts.fragment.to_string());
+                    ui.end_row();
+                });
+
+            ui.separator();
+            match &benchmark.run {
+                Some(run) => {
+                    let step = &run.steps[run.current];
+                    ui.label(format!(
+                        "Benchmarking {} at {} quality with {:?} normals ({} of {})",
+                        step.method_label,
+                        step.quality.label(),
+                        step.normal_mode,
+                        run.current + 1,
+                        run.steps.len()
+                    ));
+                }
+                None => {
+                    requested = ui.button("Run Benchmark").clicked();
+                }
+            }
+            if let Some(status) = &benchmark.status {
+                ui.label(status);
+            }
+        });
+    if requested {
+        benchmark.request();
+    }
+}
+
+/// Step through the benchmark, holding the selected water body at the current step's settings.
+#[allow(clippy::too_many_arguments)]
+fn run_benchmark(
+    mut benchmark: ResMut<Benchmark>,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<UiHistory>,
+    mut quality: ResMut<WaterQuality>,
+    stats: Res<WaterStats>,
+    gpu_pass_time: Res<GpuPassTime>,
+    methods: Res<WaterMethods>,
+    transition_settings: Res<MethodTransitionSettings>,
+    time: Res<Time>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Wait until every method's material has said whether it has anything to draw
+    let reported = methods
+        .iter()
+        .all(|method| stats.method_content.contains_key(method.name));
+    if reported && std::mem::take(&mut benchmark.requested) && benchmark.run.is_none() {
+        let (measured, empty): (Vec<&MethodInfo>, Vec<_>) = methods
+            .iter()
+            .partition(|method| stats.method_content[method.name]);
+        let steps: Vec<_> = measured
+            .into_iter()
+            .flat_map(|method| {
+                WaterQuality::ALL.into_iter().flat_map(move |quality| {
+                    [NormalMode::PerPixel, NormalMode::VertexWithDetail].map(|normal_mode| {
+                        BenchmarkStep {
+                            method: WaveMethod::named(method.name),
+                            method_label: method.label,
+                            quality,
+                            normal_mode,
+                        }
+                    })
+                })
+            })
+            .collect();
+        if steps.is_empty() {
+            benchmark.status = Some(String::from("None of the methods have anything to draw"));
+            return;
+        }
+        benchmark.status = None;
+        benchmark.run = Some(BenchmarkRun {
+            steps,
+            current: 0,
+            elapsed: 0.0,
+            frame_times: Vec::new(),
+            gpu_times: Vec::new(),
+            results: Vec::new(),
+            skipped: empty.iter().map(|method| method.label).collect(),
+            restore: ui_state.clone(),
+            restore_quality: *quality,
+        });
+    }
+    let Some(run) = &mut benchmark.run else {
+        return;
+    };
+
+    // Reapplied every frame, in case a preset finishes loading part way through
+    let step = run.steps[run.current].clone();
+    if ui_state.wave_method != step.method || ui_state.shading.normal_mode != step.normal_mode {
+        ui_state.wave_method = step.method.clone();
+        ui_state.shading.normal_mode = step.normal_mode;
+        history.follow(&ui_state);
+    }
+    quality.set_if_neq(step.quality);
+
+    // The previous method is still drawn until the transition into this one is over
+    let warmup = transition_settings.duration + BENCHMARK_WARMUP;
+    run.elapsed += time.delta_seconds();
+    if run.elapsed > warmup {
+        run.frame_times.push(time.delta_seconds() * 1000.0);
+        run.gpu_times.extend(gpu_pass_time.take_fresh());
+    } else {
+        // Readings from before the step settled would otherwise be counted when it's measured
+        gpu_pass_time.take_fresh();
+    }
+    if run.elapsed < warmup + BENCHMARK_DURATION {
+        return;
+    }
+
+    let mut frame_times = std::mem::take(&mut run.frame_times);
+    let gpu_times = std::mem::take(&mut run.gpu_times);
+    frame_times.sort_by(f32::total_cmp);
+    let mean = |times: &[f32]| times.iter().sum::<f32>() / times.len().max(1) as f32;
+    run.results.push(BenchmarkResult {
+        step,
+        frames: frame_times.len(),
+        mean_frame_time: mean(&frame_times),
+        p95_frame_time: frame_times
+            .get(frame_times.len() * 95 / 100)
+            .copied()
+            .unwrap_or_default(),
+        max_frame_time: frame_times.last().copied().unwrap_or_default(),
+        mean_gpu_time: (!gpu_times.is_empty()).then(|| mean(&gpu_times)),
+        vertices_drawn: stats.vertices_drawn(),
+        wave_counts: stats.wave_counts,
+    });
+    run.current += 1;
+    run.elapsed = 0.0;
+    if run.current < run.steps.len() {
+        return;
+    }
+
+    let Some(run) = benchmark.run.take() else {
+        return;
+    };
+    *ui_state = run.restore;
+    history.follow(&ui_state);
+    *quality = run.restore_quality;
+    let mut status = match write_report(&run.results) {
+        Ok(path) => format!("Wrote {}", path.display()),
+        Err(error) => format!("Couldn't write the benchmark report: {error}"),
+    };
+    if !run.skipped.is_empty() {
+        let _ = write!(
+            status,
+            ", skipping {} with nothing to draw",
+            run.skipped.join(", ")
+        );
+    }
+    info!("{status}");
+    benchmark.status = Some(status);
+    if benchmark.exit_when_done {
+        exit.send(AppExit);
+    }
+}
+
+/// Write the results as CSV to a new file in the benchmark directory.
+fn write_report(results: &[BenchmarkResult]) -> io::Result<PathBuf> {
+    let mut csv = String::from(
+        "method,quality,normal_mode,frames,mean_frame_ms,p95_frame_ms,max_frame_ms,mean_fps,\
+         gpu_opaque_pass_ms,water_vertices,vertex_waves,fragment_waves\n",
+    );
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{:?},{},{:.3},{:.3},{:.3},{:.1},{},{},{},{}",
+            result.step.method.name(),
+            result.step.quality.label(),
+            result.step.normal_mode,
+            result.frames,
+            result.mean_frame_time,
+            result.p95_frame_time,
+            result.max_frame_time,
+            1000.0 / result.mean_frame_time,
+            result
+                .mean_gpu_time
+                .map_or_else(String::new, |time| format!("{time:.3}")),
+            result.vertices_drawn,
+            result.wave_counts.vertex,
+            result.wave_counts.fragment,
+        );
+    }
+
+    let directory = FileAssetIo::get_base_path().join(BENCHMARK_DIRECTORY);
+    fs::create_dir_all(&directory)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = directory.join(format!("benchmark_{timestamp}.csv"));
+    fs::write(&path, csv)?;
+    Ok(path)
+}
@@ -0,0 +1,334 @@
+use bevy::{
+    asset::{AssetLoader, FileAssetIo, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    camera::CameraBookmark,
+    common, fbm_water, fft_water, load_selected_water,
+    method::{MethodMaterial, WaterMethod},
+    sum_water, ui_state_update, SelectedWater, UiState, WaveMethod,
+};
+
+const PRESET_DIRECTORY: &str = "presets";
+const PRESET_EXTENSION: &str = "ron";
+const STARTING_PRESET: &str = "open_ocean";
+/// Presets shipped with the app, which can't be deleted. The app starts with one of them.
+const BUILT_IN_PRESETS: [&str; 3] = ["calm_lake", STARTING_PRESET, "storm"];
+
+/// A complete water look: the wave method, its shading, and the settings for each method.
+///
+/// Presets are RON files under `assets/presets`, and any field left out of a file falls back to
+/// its default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid, Reflect)]
+#[serde(default)]
+#[uuid = "0b2a7a5e-8c1f-4d56-9b0e-3f6f1d5c2a47"]
+pub struct WaterPreset {
+    pub method: WaveMethod,
+    pub shading: common::Shading,
+    pub wave_type: sum_water::WaveType,
+    /// Sum of sines waves, or `None` to keep whatever waves are currently in use.
+    pub waves: Option<[sum_water::WaveSpec; sum_water::WAVE_COUNT]>,
+    pub fbm: fbm_water::FbmWaterConfig,
+    pub spectrum: fft_water::SpectrumSettings,
+    /// Saved camera views to jump between.
+    pub cameras: Vec<CameraBookmark>,
+}
+
+impl WaterPreset {
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Read a preset file directly, for tools that run without the asset server.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        WaterPreset::from_ron(&fs::read(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+fn preset_asset_path(name: &str) -> String {
+    format!("{PRESET_DIRECTORY}/{name}.{PRESET_EXTENSION}")
+}
+
+/// Where the preset the app starts with is on disk.
+pub fn starting_preset_path() -> PathBuf {
+    FileAssetIo::get_base_path()
+        .join("assets")
+        .join(preset_asset_path(STARTING_PRESET))
+}
+
+#[derive(Default)]
+pub struct WaterPresetLoader;
+
+impl AssetLoader for WaterPresetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let preset = WaterPreset::from_ron(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(preset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// The preset currently applied to the water. Whenever it is (re)loaded, e.g. because the file
+/// was edited on disk, it's applied to the live materials.
+#[derive(Resource, Debug)]
+pub struct ActivePreset {
+    /// Name of the preset file, or `None` if it has since been deleted.
+    pub name: Option<String>,
+    pub handle: Handle<WaterPreset>,
+    /// Set when a preset is chosen or reloaded, until it has loaded and reached the selected water
+    /// body's materials.
+    pending: bool,
+    /// Used in place of the next applied preset's method, e.g. one chosen on the command line.
+    method_override: Option<WaveMethod>,
+}
+
+impl ActivePreset {
+    /// Load the named preset and apply it once it's ready.
+    pub fn load(&mut self, asset_server: &AssetServer, name: &str) {
+        self.name = Some(name.to_string());
+        self.handle = asset_server.load(preset_asset_path(name));
+        self.pending = true;
+    }
+
+    /// Use `method` in place of the method of the next preset that's applied.
+    pub fn override_method(&mut self, method: WaveMethod) {
+        self.method_override = Some(method);
+    }
+
+    /// Load a preset file from anywhere, such as one given on the command line, and apply it once
+    /// it's ready.
+    pub fn load_file(&mut self, asset_server: &AssetServer, path: &Path) {
+        self.name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .map(str::to_string);
+        // The asset server looks relative paths up in `assets`, so they're made absolute first
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.handle = asset_server.load(path);
+        self.pending = true;
+    }
+
+    /// Make a preset that was just saved the active one, without re-applying it.
+    pub fn set_saved(
+        &mut self,
+        asset_server: &AssetServer,
+        presets: &mut Assets<WaterPreset>,
+        name: &str,
+        preset: WaterPreset,
+    ) {
+        self.name = Some(name.to_string());
+        self.handle = asset_server.load(preset_asset_path(name));
+        // Don't wait for the file watcher to notice the new contents
+        presets.set_untracked(&self.handle, preset);
+        self.pending = false;
+    }
+}
+
+impl FromWorld for ActivePreset {
+    fn from_world(world: &mut World) -> Self {
+        let mut active_preset = ActivePreset {
+            name: None,
+            handle: Handle::default(),
+            pending: false,
+            method_override: None,
+        };
+        active_preset.load(world.resource::<AssetServer>(), STARTING_PRESET);
+        active_preset
+    }
+}
+
+/// The preset files on disk, along with the state of the preset browser.
+#[derive(Resource, Debug)]
+pub struct PresetLibrary {
+    directory: PathBuf,
+    pub names: Vec<String>,
+    pub selected: Option<String>,
+    pub save_name: String,
+    /// Outcome of the last file operation, shown in the browser.
+    pub status: Option<String>,
+}
+
+impl Default for PresetLibrary {
+    fn default() -> Self {
+        let mut library = PresetLibrary {
+            directory: FileAssetIo::get_base_path()
+                .join("assets")
+                .join(PRESET_DIRECTORY),
+            names: Vec::new(),
+            selected: None,
+            save_name: String::new(),
+            status: None,
+        };
+        library.refresh();
+        library
+    }
+}
+
+impl PresetLibrary {
+    pub fn is_built_in(name: &str) -> bool {
+        BUILT_IN_PRESETS.contains(&name)
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ')
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{name}.{PRESET_EXTENSION}"))
+    }
+
+    /// Re-read the list of presets from disk.
+    pub fn refresh(&mut self) {
+        self.names = fs::read_dir(&self.directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == PRESET_EXTENSION)
+                    })
+                    .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.names.sort();
+    }
+
+    /// Read a preset straight from disk, rather than waiting on the asset server.
+    pub fn read(&self, name: &str) -> io::Result<WaterPreset> {
+        WaterPreset::from_ron(&fs::read(self.path(name))?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn save(&mut self, name: &str, preset: &WaterPreset) -> io::Result<()> {
+        let ron = preset
+            .to_ron()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.path(name), ron)?;
+        self.refresh();
+        Ok(())
+    }
+
+    /// Copy a preset to a new, unused name, which is returned.
+    pub fn duplicate(&mut self, name: &str) -> io::Result<String> {
+        let mut copy_name = format!("{name}_copy");
+        let mut index = 2;
+        while self.path(&copy_name).exists() {
+            copy_name = format!("{name}_copy_{index}");
+            index += 1;
+        }
+        fs::copy(self.path(name), self.path(&copy_name))?;
+        self.refresh();
+        Ok(copy_name)
+    }
+
+    pub fn delete(&mut self, name: &str) -> io::Result<()> {
+        if Self::is_built_in(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "built-in presets can't be deleted",
+            ));
+        }
+        fs::remove_file(self.path(name))?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+pub struct WaterPresetPlugin;
+
+impl Plugin for WaterPresetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaterPreset>()
+            .init_asset_loader::<WaterPresetLoader>()
+            .init_resource::<ActivePreset>()
+            .init_resource::<PresetLibrary>()
+            .add_systems(
+                Update,
+                apply_active_preset
+                    .after(load_selected_water)
+                    .before(ui_state_update),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn apply_active_preset(
+    mut events: EventReader<AssetEvent<WaterPreset>>,
+    mut active_preset: ResMut<ActivePreset>,
+    presets: Res<Assets<WaterPreset>>,
+    mut ui_state: ResMut<UiState>,
+    selected: Res<SelectedWater>,
+    bodies: Query<(
+        Option<&MethodMaterial<sum_water::SumWaterMaterial>>,
+        Option<&MethodMaterial<fft_water::FftWaterMaterial>>,
+    )>,
+    mut sum_materials: ResMut<Assets<sum_water::SumWaterMaterial>>,
+    mut fft_materials: ResMut<Assets<fft_water::FftWaterMaterial>>,
+) {
+    let reloaded = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == active_preset.handle
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if reloaded {
+        active_preset.pending = true;
+    }
+    if !active_preset.pending {
+        return;
+    }
+    let Some(preset) = presets.get(&active_preset.handle) else {
+        return;
+    };
+
+    // Presets apply to the water body being edited, so they wait until it has materials
+    let Some((sum, fft)) = selected.0.and_then(|entity| bodies.get(entity).ok()) else {
+        return;
+    };
+    let sum = sum.filter(|sum| sum_materials.contains(&sum.0));
+    let fft = fft.filter(|fft| fft_materials.contains(&fft.0));
+    if sum.is_none() && fft.is_none() {
+        return;
+    }
+    active_preset.pending = false;
+
+    ui_state.apply_preset(preset);
+    if let Some(method) = active_preset.method_override.take() {
+        ui_state.wave_method = method;
+    }
+    if let Some(waves) = &preset.waves {
+        if let Some(material) = sum.and_then(|sum| sum_materials.get_mut(&sum.0)) {
+            material.waves = waves.clone();
+        }
+    }
+    if let Some(material) = fft.and_then(|fft| fft_materials.get_mut(&fft.0)) {
+        material.apply_preset(preset);
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{method::WaterMethodSet, tile_mesh, SceneSettings, WaterMesh};
+
+/// Matches the `EFFECT_*` flags in `water_lighting.wgsl`.
+const EFFECT_REFLECTIONS: u32 = 1;
+const EFFECT_FOAM: u32 = 2;
+
+/// A single knob for how much the water costs to draw, which scales every method's detail
+/// together for settings menus.
+///
+/// `High` draws presets as they were made. Lower tiers cap their detail and drop optional
+/// effects. `Ultra` draws a denser mesh and adds FBM octaves past a preset's; the sum of sines
+/// already draws every wave it has room for at `High`.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub enum WaterQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Ultra,
+}
+
+impl WaterQuality {
+    pub const ALL: [WaterQuality; 4] = [
+        WaterQuality::Low,
+        WaterQuality::Medium,
+        WaterQuality::High,
+        WaterQuality::Ultra,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WaterQuality::Low => "Low",
+            WaterQuality::Medium => "Medium",
+            WaterQuality::High => "High",
+            WaterQuality::Ultra => "Ultra",
+        }
+    }
+
+    /// The tier with the given label, ignoring case.
+    pub fn from_label(label: &str) -> Option<Self> {
+        WaterQuality::ALL
+            .into_iter()
+            .find(|quality| quality.label().eq_ignore_ascii_case(label))
+    }
+
+    pub fn settings(self) -> QualitySettings {
+        match self {
+            WaterQuality::Low => QualitySettings {
+                mesh_density: 0.25,
+                fbm_vertex_octaves: Some(8),
+                fbm_fragment_octaves: Some(12),
+                fbm_extra_octaves: 0,
+                sum_wave_count: 2,
+                fft_resolution: 64,
+                fft_cascades: 1,
+                reflections: false,
+                foam: false,
+            },
+            WaterQuality::Medium => QualitySettings {
+                mesh_density: 0.5,
+                fbm_vertex_octaves: Some(16),
+                fbm_fragment_octaves: Some(24),
+                fbm_extra_octaves: 0,
+                sum_wave_count: 3,
+                fft_resolution: 128,
+                fft_cascades: 2,
+                reflections: true,
+                foam: false,
+            },
+            WaterQuality::High => QualitySettings {
+                mesh_density: 1.0,
+                fbm_vertex_octaves: None,
+                fbm_fragment_octaves: None,
+                fbm_extra_octaves: 0,
+                sum_wave_count: 4,
+                fft_resolution: 256,
+                fft_cascades: 3,
+                reflections: true,
+                foam: true,
+            },
+            WaterQuality::Ultra => QualitySettings {
+                mesh_density: 2.0,
+                fbm_vertex_octaves: None,
+                fbm_fragment_octaves: None,
+                fbm_extra_octaves: 8,
+                sum_wave_count: 4,
+                fft_resolution: 512,
+                fft_cascades: 4,
+                reflections: true,
+                foam: true,
+            },
+        }
+    }
+}
+
+/// What a `WaterQuality` tier sets, for the materials to draw with.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct QualitySettings {
+    /// Scale on the scene's quads per metre.
+    pub mesh_density: f32,
+    /// Most octaves the FBM method sums per vertex and per pixel, or `None` for as many as its
+    /// config asks for.
+    pub fbm_vertex_octaves: Option<usize>,
+    pub fbm_fragment_octaves: Option<usize>,
+    /// Octaves the FBM method adds past its config's, per vertex and per pixel, up to
+    /// `MAX_OCTAVES`.
+    pub fbm_extra_octaves: usize,
+    /// How many of the sum of sines waves are drawn.
+    pub sum_wave_count: usize,
+    /// Texels along each side of the FFT method's spectrum, and how many cascades of it are
+    /// layered at different scales. Nothing reads these until that method simulates its spectrum,
+    /// so for now the tiers only change its mesh and effects.
+    pub fft_resolution: u32,
+    pub fft_cascades: u32,
+    /// Whether the Fresnel sky reflection is drawn.
+    pub reflections: bool,
+    /// Whether wave tips and baked foam are drawn.
+    pub foam: bool,
+}
+
+impl QualitySettings {
+    /// The optional effects as the shaders' `effects` flags.
+    pub fn effects(&self) -> u32 {
+        let mut effects = 0;
+        if self.reflections {
+            effects |= EFFECT_REFLECTIONS;
+        }
+        if self.foam {
+            effects |= EFFECT_FOAM;
+        }
+        effects
+    }
+}
+
+impl Default for QualitySettings {
+    fn default() -> Self {
+        WaterQuality::default().settings()
+    }
+}
+
+pub struct WaterQualityPlugin;
+
+impl Plugin for WaterQualityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterQuality>()
+            .register_type::<WaterQuality>()
+            .add_systems(
+                Update,
+                (
+                    quality_ui,
+                    update_water_mesh.run_if(resource_changed::<WaterQuality>()),
+                )
+                    .chain()
+                    .before(WaterMethodSet::Update),
+            );
+    }
+}
+
+fn quality_ui(mut contexts: EguiContexts, mut quality: ResMut<WaterQuality>) {
+    let mut edited = *quality;
+    egui::Window::new("Quality")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for tier in WaterQuality::ALL {
+                    ui.radio_value(&mut edited, tier, tier.label());
+                }
+            });
+        });
+    quality.set_if_neq(edited);
+}
+
+/// Rebuild the tile mesh every water tile shares at the tier's density.
+fn update_water_mesh(
+    quality: Res<WaterQuality>,
+    scene: Res<SceneSettings>,
+    water_mesh: Option<ResMut<WaterMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Some(mut water_mesh) = water_mesh else {
+        return;
+    };
+    let quad_res = scene.quad_res * quality.settings().mesh_density;
+    let (mesh, quads_per_side) = tile_mesh(water_mesh.tile_length, quad_res);
+    if quads_per_side == water_mesh.quads_per_side {
+        return;
+    }
+    if let Some(tile) = meshes.get_mut(&water_mesh.mesh) {
+        *tile = mesh;
+        water_mesh.quads_per_side = quads_per_side;
+    }
+}
@@ -0,0 +1,609 @@
+use bevy::{
+    prelude::*,
+    reflect::{ReflectMut, ReflectRef},
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    common, fbm_water, fft_water,
+    history::UiHistory,
+    load_selected_water,
+    method::{MethodMaterial, WaterMethod},
+    preset::WaterPreset,
+    sum_water, ui_state_update,
+    weather::Weather,
+    SelectedWater, UiState, WaterBody, WaveMethod,
+};
+
+/// Seconds between a new keyframe and the one before it.
+pub const KEYFRAME_SPACING: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+    /// Hold the previous keyframe, then jump.
+    Step,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Step,
+    ];
+
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease In",
+            Easing::EaseOut => "Ease Out",
+            Easing::EaseInOut => "Ease In-Out",
+            Easing::Step => "Step",
+        }
+    }
+}
+
+/// A water state that a sequence reaches at a given time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the sequence.
+    pub time: f32,
+    /// How the tween from the previous keyframe eases into this one.
+    pub easing: Easing,
+    pub preset: WaterPreset,
+    /// The weather to reach as well, or `None` to leave it alone.
+    pub weather: Option<Weather>,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, preset: WaterPreset) -> Self {
+        Keyframe {
+            time,
+            easing: Easing::default(),
+            preset,
+            weather: None,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_weather(mut self, weather: Weather) -> Self {
+        self.weather = Some(weather);
+        self
+    }
+}
+
+/// Keyframed water states played back on a water body, such as a storm rolling in.
+///
+/// Every number in the settings is tweened from one keyframe to the next, and anything that
+/// can't be, like the wave method, switches halfway. Insert one on a water body to play it, e.g.
+/// `WaterSequence::tween(calm, storm, 30.0, Easing::EaseInOut).playing()`.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct WaterSequence {
+    keyframes: Vec<Keyframe>,
+    /// The playhead, in seconds from the start.
+    pub elapsed: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl WaterSequence {
+    /// A sequence going from one state to another.
+    pub fn tween(from: WaterPreset, to: WaterPreset, duration: f32, easing: Easing) -> Self {
+        WaterSequence::default()
+            .with_keyframe(Keyframe::new(0.0, from))
+            .with_keyframe(Keyframe::new(duration, to).with_easing(easing))
+    }
+
+    pub fn with_keyframe(mut self, keyframe: Keyframe) -> Self {
+        self.insert(keyframe);
+        self
+    }
+
+    pub fn playing(mut self) -> Self {
+        self.playing = true;
+        self
+    }
+
+    /// Add a keyframe, keeping them in time order.
+    pub fn insert(&mut self, keyframe: Keyframe) -> usize {
+        let index = self
+            .keyframes
+            .partition_point(|other| other.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+        index
+    }
+
+    pub fn remove(&mut self, index: usize) -> Keyframe {
+        self.keyframes.remove(index)
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The state at `time`, holding the first and last keyframes outside of the sequence.
+    pub fn sample(&self, time: f32) -> Option<(WaterPreset, Option<Weather>)> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        let (from, to) = match next {
+            0 => {
+                let first = self.keyframes.first()?;
+                return Some((first.preset.clone(), first.weather.clone()));
+            }
+            _ if next == self.keyframes.len() => {
+                let last = &self.keyframes[next - 1];
+                return Some((last.preset.clone(), last.weather.clone()));
+            }
+            _ => (&self.keyframes[next - 1], &self.keyframes[next]),
+        };
+        let t = to
+            .easing
+            .apply((time - from.time) / (to.time - from.time).max(f32::EPSILON));
+        Some((
+            tween(&from.preset, &to.preset, t),
+            tween(&from.weather, &to.weather, t),
+        ))
+    }
+}
+
+/// Blend between two values of any reflected type.
+pub fn tween<T: Reflect + Clone>(from: &T, to: &T, t: f32) -> T {
+    let mut value = from.clone();
+    tween_reflect(&mut value, to, t);
+    value
+}
+
+/// Blend every number in `value` towards the matching one in `to`. Colors are blended as they
+/// appear in the color pickers, and anything else that can't be blended, such as a different
+/// enum variant or a name, switches over halfway.
+pub fn tween_reflect(value: &mut dyn Reflect, to: &dyn Reflect, t: f32) {
+    if let (Some(value), Some(to)) = (value.downcast_mut::<f32>(), to.downcast_ref::<f32>()) {
+        *value += (*to - *value) * t;
+        return;
+    }
+    if let (Some(value), Some(to)) = (value.downcast_mut::<usize>(), to.downcast_ref::<usize>()) {
+        *value = (*value as f32 + (*to as f32 - *value as f32) * t).round() as usize;
+        return;
+    }
+    if let (Some(value), Some(to)) = (value.downcast_mut::<Color>(), to.downcast_ref::<Color>()) {
+        let [r, g, b, a] = Vec4::from(value.as_rgba_f32())
+            .lerp(Vec4::from(to.as_rgba_f32()), t)
+            .to_array();
+        *value = Color::rgba(r, g, b, a);
+        return;
+    }
+
+    let blended = match (value.reflect_mut(), to.reflect_ref()) {
+        (ReflectMut::Struct(value), ReflectRef::Struct(to)) => {
+            for index in 0..value.field_len() {
+                if let (Some(field), Some(to)) = (value.field_at_mut(index), to.field_at(index)) {
+                    tween_reflect(field, to, t);
+                }
+            }
+            true
+        }
+        (ReflectMut::TupleStruct(value), ReflectRef::TupleStruct(to)) => {
+            for index in 0..value.field_len() {
+                if let (Some(field), Some(to)) = (value.field_mut(index), to.field(index)) {
+                    tween_reflect(field, to, t);
+                }
+            }
+            true
+        }
+        (ReflectMut::Tuple(value), ReflectRef::Tuple(to)) => {
+            for index in 0..value.field_len() {
+                if let (Some(field), Some(to)) = (value.field_mut(index), to.field(index)) {
+                    tween_reflect(field, to, t);
+                }
+            }
+            true
+        }
+        (ReflectMut::Array(value), ReflectRef::Array(to)) if value.len() == to.len() => {
+            for index in 0..value.len() {
+                if let (Some(item), Some(to)) = (value.get_mut(index), to.get(index)) {
+                    tween_reflect(item, to, t);
+                }
+            }
+            true
+        }
+        (ReflectMut::List(value), ReflectRef::List(to)) if value.len() == to.len() => {
+            for index in 0..value.len() {
+                if let (Some(item), Some(to)) = (value.get_mut(index), to.get(index)) {
+                    tween_reflect(item, to, t);
+                }
+            }
+            true
+        }
+        (ReflectMut::Enum(value), ReflectRef::Enum(to))
+            if value.variant_name() == to.variant_name() =>
+        {
+            for index in 0..value.field_len() {
+                if let (Some(field), Some(to)) = (value.field_at_mut(index), to.field_at(index)) {
+                    tween_reflect(field, to, t);
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+    if !blended && t >= 0.5 {
+        value.apply(to);
+    }
+}
+
+pub struct WaterSequencePlugin;
+
+impl Plugin for WaterSequencePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Easing>().add_systems(
+            Update,
+            (sequence_ui, advance_sequences, apply_sequences)
+                .chain()
+                .after(load_selected_water)
+                .before(ui_state_update),
+        );
+    }
+}
+
+fn advance_sequences(mut sequences: Query<&mut WaterSequence>, time: Res<Time>) {
+    for mut sequence in sequences.iter_mut() {
+        if !sequence.playing {
+            continue;
+        }
+        let duration = sequence.duration();
+        sequence.elapsed += time.delta_seconds();
+        if sequence.elapsed >= duration {
+            if sequence.looping && duration > 0.0 {
+                sequence.elapsed %= duration;
+            } else {
+                sequence.elapsed = duration;
+                sequence.playing = false;
+            }
+        }
+    }
+}
+
+/// Set each water body to its sequence's state whenever the playhead moves or the keyframes are
+/// edited. The settings each method keeps in its own material follow in
+/// [`apply_sequence_material`], and only the selected body's sequence moves the shared weather.
+#[allow(clippy::type_complexity)]
+pub fn apply_sequences(
+    sequences: Query<(Entity, &WaterSequence), Changed<WaterSequence>>,
+    mut bodies: Query<
+        (
+            &mut WaveMethod,
+            &mut sum_water::WaveType,
+            &mut common::Shading,
+            &mut fbm_water::FbmWaterConfig,
+        ),
+        With<WaterBody>,
+    >,
+    selected: Res<SelectedWater>,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<UiHistory>,
+    mut weather: ResMut<Weather>,
+) {
+    for (entity, sequence) in sequences.iter() {
+        let Some((preset, sequence_weather)) = sequence.sample(sequence.elapsed) else {
+            continue;
+        };
+        if selected.0 == Some(entity) {
+            // Go through the settings window so it shows the sequence, without filling the
+            // undo history with every frame of it
+            ui_state.apply_preset(&preset);
+            history.follow(&ui_state);
+            if let Some(sequence_weather) = sequence_weather {
+                weather.set_if_neq(sequence_weather);
+            }
+        } else if let Ok((mut wave_method, mut wave_type, mut shading, mut fbm_config)) =
+            bodies.get_mut(entity)
+        {
+            wave_method.set_if_neq(preset.method.clone());
+            wave_type.set_if_neq(preset.wave_type);
+            shading.set_if_neq(preset.shading.clone());
+            fbm_config.set_if_neq(preset.fbm.clone());
+        }
+    }
+}
+
+/// Hand each water body's sequence state to its `M` material, for the settings that only the
+/// material keeps.
+pub fn apply_sequence_material<M: WaterMethod>(
+    sequences: Query<(&WaterSequence, &MethodMaterial<M>), Changed<WaterSequence>>,
+    mut materials: ResMut<Assets<M>>,
+) {
+    for (sequence, material) in sequences.iter() {
+        let Some((preset, _)) = sequence.sample(sequence.elapsed) else {
+            continue;
+        };
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.apply_preset(&preset);
+        }
+    }
+}
+
+/// Settings of the sequence window that aren't part of any sequence.
+struct SequenceEditor {
+    key_weather: bool,
+}
+
+impl Default for SequenceEditor {
+    fn default() -> Self {
+        SequenceEditor { key_weather: true }
+    }
+}
+
+/// Timeline of the selected water body's sequence, where its current settings can be keyed and
+/// the result previewed.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn sequence_ui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut editor: Local<SequenceEditor>,
+    selected: Res<SelectedWater>,
+    sequences: Query<&WaterSequence>,
+    bodies: Query<(
+        Option<&MethodMaterial<sum_water::SumWaterMaterial>>,
+        Option<&MethodMaterial<fft_water::FftWaterMaterial>>,
+    )>,
+    sum_materials: Res<Assets<sum_water::SumWaterMaterial>>,
+    fft_materials: Res<Assets<fft_water::FftWaterMaterial>>,
+    ui_state: Res<UiState>,
+    weather: Res<Weather>,
+) {
+    let Some(entity) = selected.0 else {
+        return;
+    };
+    let current = sequences.get(entity).ok();
+    let mut sequence = current.cloned().unwrap_or_default();
+    let mut clear = false;
+
+    egui::Window::new("Sequence")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let duration = sequence.duration();
+            ui.horizontal(|ui| {
+                let play_label = if sequence.playing { "Pause" } else { "Play" };
+                if ui
+                    .add_enabled(duration > 0.0, egui::Button::new(play_label))
+                    .clicked()
+                {
+                    if !sequence.playing && sequence.elapsed >= duration {
+                        sequence.elapsed = 0.0;
+                    }
+                    sequence.playing = !sequence.playing;
+                }
+                if ui.button("Stop").clicked() {
+                    sequence.playing = false;
+                    sequence.elapsed = 0.0;
+                }
+                ui.checkbox(&mut sequence.looping, "Loop");
+            });
+
+            let scrub = ui.add(
+                egui::Slider::new(&mut sequence.elapsed, 0.0..=duration)
+                    .suffix(" s")
+                    .text("Time"),
+            );
+            if scrub.dragged() {
+                sequence.playing = false;
+            }
+
+            ui.separator();
+            let mut remove = None;
+            let mut reorder = false;
+            egui::Grid::new("keyframes")
+                .num_columns(4)
+                .spacing([8.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for (index, keyframe) in sequence.keyframes.iter_mut().enumerate() {
+                        let time = ui.add(
+                            egui::DragValue::new(&mut keyframe.time)
+                                .speed(0.1)
+                                .clamp_range(0.0..=f32::MAX)
+                                .suffix(" s"),
+                        );
+                        reorder |= time.changed();
+                        egui::ComboBox::from_id_source(("easing", index))
+                            .selected_text(keyframe.easing.label())
+                            .show_ui(ui, |ui| {
+                                for easing in Easing::ALL {
+                                    ui.selectable_value(
+                                        &mut keyframe.easing,
+                                        easing,
+                                        easing.label(),
+                                    );
+                                }
+                            });
+                        if ui.button("Go To").clicked() {
+                            sequence.elapsed = keyframe.time;
+                            sequence.playing = false;
+                        }
+                        if ui.button("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(index) = remove {
+                sequence.remove(index);
+            }
+            if reorder {
+                sequence.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Keyframe").clicked() {
+                    let (sum, fft) = bodies.get(entity).unwrap_or((None, None));
+                    let preset = ui_state.to_preset(
+                        sum.and_then(|sum| sum_materials.get(&sum.0)),
+                        fft.and_then(|fft| fft_materials.get(&fft.0)),
+                    );
+                    let time = if sequence.keyframes.is_empty() {
+                        0.0
+                    } else {
+                        sequence.duration() + KEYFRAME_SPACING
+                    };
+                    let mut keyframe = Keyframe::new(time, preset);
+                    if editor.key_weather {
+                        keyframe = keyframe.with_weather(weather.clone());
+                    }
+                    sequence.insert(keyframe);
+                    // Park the playhead on the new keyframe, which is what's showing already
+                    sequence.elapsed = time;
+                    sequence.playing = false;
+                }
+                ui.checkbox(&mut editor.key_weather, "Key Weather");
+                if ui
+                    .add_enabled(current.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    clear = true;
+                }
+            });
+        });
+
+    if clear {
+        commands.entity(entity).remove::<WaterSequence>();
+    } else if current.map_or(!sequence.keyframes.is_empty(), |current| {
+        *current != sequence
+    }) {
+        // Only write back real edits, since any change re-applies the sequence to the water
+        commands.entity(entity).insert(sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fbm_water::FbmWaterMaterial;
+    use crate::sum_water::SumWaterMaterial;
+
+    fn preset(shininess: f32) -> WaterPreset {
+        let mut preset = WaterPreset::default();
+        preset.shading.shininess = shininess;
+        preset
+    }
+
+    fn shininess_at(sequence: &WaterSequence, time: f32) -> f32 {
+        sequence.sample(time).unwrap().0.shading.shininess
+    }
+
+    #[test]
+    fn easings_start_and_end_at_the_keyframes() {
+        for easing in Easing::ALL {
+            assert_eq!(easing.apply(0.0), 0.0, "{}", easing.label());
+            assert_eq!(easing.apply(1.0), 1.0, "{}", easing.label());
+        }
+    }
+
+    #[test]
+    fn easing_curves() {
+        assert_eq!(Easing::Linear.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.25), 0.15625);
+        assert_eq!(Easing::Step.apply(0.99), 0.0);
+        // Out of range times are clamped
+        assert_eq!(Easing::EaseIn.apply(2.0), 1.0);
+    }
+
+    #[test]
+    fn sample_eases_into_the_next_keyframe() {
+        let sequence = WaterSequence::tween(preset(10.0), preset(30.0), 10.0, Easing::EaseIn);
+        assert_eq!(shininess_at(&sequence, 0.0), 10.0);
+        assert_eq!(shininess_at(&sequence, 5.0), 15.0);
+        assert_eq!(shininess_at(&sequence, 10.0), 30.0);
+    }
+
+    #[test]
+    fn sample_holds_the_first_and_last_keyframes() {
+        let sequence = WaterSequence::tween(preset(10.0), preset(30.0), 10.0, Easing::Linear)
+            .with_keyframe(Keyframe::new(20.0, preset(50.0)));
+        assert_eq!(shininess_at(&sequence, -1.0), 10.0);
+        assert_eq!(shininess_at(&sequence, 15.0), 40.0);
+        assert_eq!(shininess_at(&sequence, 25.0), 50.0);
+        assert!(WaterSequence::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn sample_tweens_keyed_weather() {
+        let calm = Weather {
+            wind_speed: 0.0,
+            ..default()
+        };
+        let windy = Weather {
+            wind_speed: 20.0,
+            ..default()
+        };
+        let sequence = WaterSequence::default()
+            .with_keyframe(Keyframe::new(0.0, preset(10.0)).with_weather(calm))
+            .with_keyframe(
+                Keyframe::new(10.0, preset(10.0))
+                    .with_easing(Easing::Linear)
+                    .with_weather(windy),
+            );
+        let (_, weather) = sequence.sample(5.0).unwrap();
+        assert_eq!(weather.unwrap().wind_speed, 10.0);
+    }
+
+    #[test]
+    fn enums_switch_halfway() {
+        let mut from = preset(10.0);
+        from.method = WaveMethod::of::<FbmWaterMaterial>();
+        from.wave_type = sum_water::WaveType::Sine;
+        let mut to = from.clone();
+        to.method = WaveMethod::of::<SumWaterMaterial>();
+        to.wave_type = sum_water::WaveType::SteepSine;
+
+        let before = tween(&from, &to, 0.49);
+        assert!(before.method.is::<FbmWaterMaterial>());
+        assert_eq!(before.wave_type, sum_water::WaveType::Sine);
+        let after = tween(&from, &to, 0.5);
+        assert!(after.method.is::<SumWaterMaterial>());
+        assert_eq!(after.wave_type, sum_water::WaveType::SteepSine);
+    }
+
+    #[test]
+    fn tween_blends_colors_and_counts() {
+        let color = tween(
+            &Color::rgba(0.0, 0.0, 0.0, 1.0),
+            &Color::rgba(1.0, 0.5, 0.0, 1.0),
+            0.5,
+        );
+        assert_eq!(color, Color::rgba(0.5, 0.25, 0.0, 1.0));
+        assert_eq!(tween(&8_usize, &16_usize, 0.25), 10);
+        assert_eq!(tween(&8_usize, &16_usize, 0.3), 10);
+    }
+}
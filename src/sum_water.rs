@@ -1,15 +1,26 @@
 use bevy::{
     prelude::*,
-    reflect::{TypePath, TypeUuid},
+    reflect::TypeUuid,
     render::{
         render_asset::RenderAssets,
         render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType},
     },
 };
+use bevy_egui::egui;
 use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{f32::consts::PI, ops::RangeInclusive};
 
-const WAVE_COUNT: usize = 4;
+use crate::{
+    common,
+    method::{MethodMaterial, WaterMethod},
+    preset::WaterPreset,
+    quality::QualitySettings,
+    weather::Weather,
+    UiState,
+};
+
+pub const WAVE_COUNT: usize = 4;
 const MEDIAN_WAVELENGTH: f32 = 1.0;
 const WAVELENGTH_RANGE: f32 = 1.0;
 const MEDIAN_DIRECTION: f32 = 0.0;
@@ -18,14 +29,15 @@ const MEDIAN_AMPLITUDE: f32 = 0.1;
 const MEDIAN_SPEED: f32 = 0.5;
 const SPEED_RANGE: f32 = 0.1;
 
-#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
 pub enum WaveType {
     Sine = 0,
     #[default]
     SteepSine = 1,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
 pub struct WaveSpec {
     pub ty: WaveType,
     direction: Vec2,
@@ -35,16 +47,6 @@ pub struct WaveSpec {
     steepness: f32,
 }
 
-impl From<WaveSpec> for Mat3 {
-    fn from(wave: WaveSpec) -> Self {
-        Mat3::from_cols(
-            Vec3::new(wave.direction.x, wave.direction.y, wave.frequency),
-            Vec3::new(wave.amplitude, wave.phase, wave.steepness),
-            Vec3::new((wave.ty as u32) as f32, 0.0, 0.0),
-        )
-    }
-}
-
 impl Default for WaveSpec {
     fn default() -> Self {
         WaveSpec::new(WaveType::Sine, 0.0, 1.0, 1.0, 1.0, 1.0)
@@ -88,6 +90,28 @@ impl WaveSpec {
         let steepness = 2.0;
         WaveSpec::new(ty, direction, speed, amplitude, wavelength, steepness)
     }
+
+    /// The wave packed for the shader, with its phase on `clock` in place of its speed.
+    fn packed(&self, clock: &common::WaveClock) -> Mat3 {
+        Mat3::from_cols(
+            Vec3::new(self.direction.x, self.direction.y, self.frequency),
+            Vec3::new(self.amplitude, clock.phase(self.phase), self.steepness),
+            Vec3::new((self.ty as u32) as f32, 0.0, 0.0),
+        )
+    }
+
+    /// The wave as the weather shapes it: turned with the wind and spread around it by the gusts,
+    /// taller in rougher seas and faster in stronger wind.
+    pub fn with_weather(&self, weather: &Weather) -> Self {
+        let offset = self.direction.y.atan2(self.direction.x) - MEDIAN_DIRECTION;
+        let direction = MEDIAN_DIRECTION + weather.wind_angle() + offset * weather.spread_scale();
+        WaveSpec {
+            direction: Vec2::new(direction.cos(), direction.sin()),
+            amplitude: self.amplitude * weather.height_scale(),
+            phase: self.phase * weather.speed_scale(),
+            ..self.clone()
+        }
+    }
 }
 
 fn random_f32_range(rng: &mut GlobalRng, range: RangeInclusive<f32>) -> f32 {
@@ -95,13 +119,17 @@ fn random_f32_range(rng: &mut GlobalRng, range: RangeInclusive<f32>) -> f32 {
 }
 
 /// "Sum of Sines" based water material.
-#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone)]
+#[derive(AsBindGroup, TypeUuid, Reflect, Debug, Clone, PartialEq)]
 #[uniform(0, WaterMaterialUniform)]
 #[uuid = "d3a49f45-e0ab-49bb-bc8c-bdb020d289a6"]
 pub struct SumWaterMaterial {
-    pub time: f32,
+    pub clock: common::WaveClock,
     pub waves: [WaveSpec; WAVE_COUNT],
     pub shading: super::common::Shading,
+    pub fade: super::common::Fade,
+    pub debug_draw: super::common::DebugDraw,
+    pub quality: QualitySettings,
+    pub weather: Weather,
 }
 
 impl SumWaterMaterial {
@@ -112,9 +140,13 @@ impl SumWaterMaterial {
             v.try_into().unwrap()
         };
         SumWaterMaterial {
-            time: 0.0,
+            clock: common::WaveClock::default(),
             waves,
             shading: super::common::Shading::default(),
+            fade: super::common::Fade::default(),
+            debug_draw: super::common::DebugDraw::default(),
+            quality: QualitySettings::default(),
+            weather: Weather::default(),
         }
     }
 
@@ -126,11 +158,158 @@ impl SumWaterMaterial {
             v.try_into().unwrap()
         };
     }
+
+    /// How many of the waves the quality tier draws.
+    pub fn wave_count(&self) -> usize {
+        self.quality.sum_wave_count.min(WAVE_COUNT)
+    }
+
+    /// The waves the shader actually draws, flattened past the quality tier's wave count.
+    pub fn weathered_waves(&self) -> [WaveSpec; WAVE_COUNT] {
+        let wave_count = self.wave_count();
+        let mut index = 0;
+        self.waves.clone().map(|wave| {
+            let mut wave = wave.with_weather(&self.weather);
+            if index >= wave_count {
+                wave.amplitude = 0.0;
+            }
+            index += 1;
+            wave
+        })
+    }
+}
+
+impl WaterMethod for SumWaterMaterial {
+    const NAME: &'static str = "SumOfSines";
+    const LABEL: &'static str = "Sum of Sines";
+
+    fn new_material(rng: &mut GlobalRng) -> Self {
+        SumWaterMaterial::random(WaveType::default(), rng)
+    }
+
+    fn set_clock(&mut self, clock: common::WaveClock) {
+        self.clock = clock;
+    }
+
+    fn apply_shading(&mut self, shading: &super::common::Shading) {
+        self.shading = shading.clone();
+    }
+
+    fn set_fade(&mut self, fade: super::common::Fade) {
+        self.fade = fade;
+    }
+
+    fn set_debug_draw(&mut self, debug_draw: super::common::DebugDraw) {
+        self.debug_draw = debug_draw;
+    }
+
+    fn set_quality(&mut self, quality: QualitySettings) {
+        self.quality = quality;
+    }
+
+    fn apply_weather(&mut self, weather: &Weather) {
+        self.weather = weather.clone();
+    }
+
+    fn displacement_bounds(&self) -> (Vec3, Vec3) {
+        let (min, max) = self
+            .weathered_waves()
+            .iter()
+            .map(|wave| match wave.ty {
+                WaveType::Sine => (-wave.amplitude.abs(), wave.amplitude.abs()),
+                WaveType::SteepSine => {
+                    let peak = 2.0 * wave.amplitude;
+                    (peak.min(0.0), peak.max(0.0))
+                }
+            })
+            .fold((0.0, 0.0), |(min, max), (wave_min, wave_max)| {
+                (min + wave_min, max + wave_max)
+            });
+        (Vec3::new(0.0, min, 0.0), Vec3::new(0.0, max, 0.0))
+    }
+
+    fn wave_counts(&self) -> common::WaveCounts {
+        common::WaveCounts {
+            vertex: self.wave_count() as u32,
+            fragment: self.shading.fragment_wave_count(self.wave_count() as u32),
+        }
+    }
+
+    fn height_at(&self, surface_position: Vec2) -> f32 {
+        self.weathered_waves()
+            .iter()
+            .map(|wave| {
+                let x = wave.direction.dot(surface_position) * wave.frequency
+                    + self.clock.phase(wave.phase);
+                match wave.ty {
+                    WaveType::Sine => wave.amplitude * x.sin(),
+                    WaveType::SteepSine => {
+                        2.0 * wave.amplitude * ((x.sin() + 1.0) / 2.0).powf(wave.steepness)
+                    }
+                }
+            })
+            .sum()
+    }
+
+    fn apply_preset(&mut self, preset: &WaterPreset) {
+        if let Some(waves) = &preset.waves {
+            self.waves = waves.clone();
+        }
+        for wave in self.waves.iter_mut() {
+            wave.ty = preset.wave_type;
+        }
+    }
+
+    fn draw_ui(&mut self, ui: &mut egui::Ui, ui_state: &mut UiState, rng: &mut GlobalRng) {
+        // TODO: Add wave settings
+
+        egui::Grid::new("sum_of_sines")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Wave Type");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut ui_state.wave_type, WaveType::Sine, "Sine");
+                    ui.radio_value(&mut ui_state.wave_type, WaveType::SteepSine, "Steep Sine");
+                });
+                ui.end_row();
+
+                ui.label("");
+                let button = ui.button("Regenerate Waves");
+                if button.clicked() {
+                    self.randomize(rng);
+                }
+                ui.end_row();
+            });
+    }
+
+    fn build(app: &mut App) {
+        app.add_systems(Update, update_wave_type);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_wave_type(
+    mut sum_materials: ResMut<Assets<SumWaterMaterial>>,
+    bodies: Query<
+        (&MethodMaterial<SumWaterMaterial>, &WaveType),
+        Or<(Changed<WaveType>, Added<MethodMaterial<SumWaterMaterial>>)>,
+    >,
+) {
+    for (material, wave_type) in bodies.iter() {
+        let Some(material) = sum_materials.get_mut(&material.0) else {
+            continue;
+        };
+        for wave in material.waves.iter_mut() {
+            wave.ty = *wave_type;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, ShaderType)]
 struct WaterMaterialUniform {
-    time: f32,
+    detail_phases: Vec3,
     waves: [Mat3; WAVE_COUNT],
     ambient: Color,
     diffuse_reflectance: Color,
@@ -142,13 +321,23 @@ struct WaterMaterialUniform {
     fresnel_shininess: f32,
     tip_attenuation: f32,
     tip_color: Color,
+    normal_mode: u32,
+    detail_strength: f32,
+    detail_frequency: f32,
+    fade_min: f32,
+    fade_max: f32,
+    fade_amplitude: f32,
+    debug_view: u32,
+    wireframe_quads: u32,
+    effects: u32,
+    wave_count: u32,
 }
 
 impl AsBindGroupShaderType<WaterMaterialUniform> for SumWaterMaterial {
     fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> WaterMaterialUniform {
         WaterMaterialUniform {
-            time: self.time,
-            waves: self.waves.clone().map(Mat3::from),
+            detail_phases: self.clock.detail_phases(self.shading.detail_frequency),
+            waves: self.weathered_waves().map(|wave| wave.packed(&self.clock)),
             ambient: self.shading.ambient,
             diffuse_reflectance: self.shading.diffuse_reflectance,
             specular_reflectance: self.shading.specular_reflectance,
@@ -159,6 +348,16 @@ impl AsBindGroupShaderType<WaterMaterialUniform> for SumWaterMaterial {
             fresnel_shininess: self.shading.fresnel.shininess,
             tip_attenuation: self.shading.tip_attenuation,
             tip_color: self.shading.tip_color,
+            normal_mode: self.shading.normal_mode as u32,
+            detail_strength: self.shading.detail_strength,
+            detail_frequency: self.shading.detail_frequency,
+            fade_min: self.fade.min,
+            fade_max: self.fade.max,
+            fade_amplitude: self.fade.amplitude,
+            debug_view: self.debug_draw.view as u32,
+            wireframe_quads: self.debug_draw.wireframe_quads,
+            effects: self.quality.effects(),
+            wave_count: self.wave_count() as u32,
         }
     }
 }
@@ -171,4 +370,12 @@ impl Material for SumWaterMaterial {
     fn fragment_shader() -> ShaderRef {
         "shaders/sum_water_material.wgsl".into()
     }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/sum_water_prepass.wgsl".into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/sum_water_prepass.wgsl".into()
+    }
 }
@@ -0,0 +1,391 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    common::WaveClock,
+    method::{WaterMethod, WaterMethodSet},
+    SelectedWater, WaterBody,
+};
+
+/// Heights within this many metres of each other count as the same when checking a loop.
+pub const LOOP_TOLERANCE: f32 = 1e-3;
+/// Period a newly enabled loop starts with, in seconds.
+const DEFAULT_LOOP_PERIOD: f64 = 10.0;
+/// Sample points per side of the grid a loop is checked over, and how far it reaches.
+const LOOP_CHECK_GRID: usize = 9;
+const LOOP_CHECK_EXTENT: f32 = 50.0;
+
+/// The clock the water animates by, separate from the app's so it can be paused, slowed down,
+/// run backwards or set outright.
+///
+/// It's kept in f64 and never wraps. Materials turn it into wave phases on the CPU, so the water
+/// neither loses precision nor jumps however long it runs.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct WaterTime {
+    pub paused: bool,
+    /// How many seconds of water time pass per real second. Negative values rewind.
+    pub speed: f32,
+    /// When set, the water shows this time instead of its own clock, e.g. for a cinematic that
+    /// needs every frame to be the same each time it plays.
+    pub override_time: Option<f64>,
+    /// Makes the water repeat exactly after this many seconds, for baking animations that loop.
+    pub loop_period: Option<f64>,
+    elapsed: f64,
+}
+
+impl Default for WaterTime {
+    fn default() -> Self {
+        WaterTime {
+            paused: false,
+            speed: 1.0,
+            override_time: None,
+            loop_period: None,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl WaterTime {
+    /// The time the water is showing, in seconds.
+    pub fn elapsed(&self) -> f64 {
+        self.override_time.unwrap_or(self.elapsed)
+    }
+
+    /// The clock a water body shifted by `offset` seconds animates to.
+    pub fn clock(&self, offset: f32) -> WaveClock {
+        WaveClock {
+            time: self.elapsed() + offset as f64,
+            loop_period: self.loop_period,
+        }
+    }
+
+    /// Move the clock to `time`, carrying on from there.
+    pub fn set_elapsed(&mut self, time: f64) {
+        self.elapsed = time;
+    }
+
+    fn advance(&mut self, delta: f64) {
+        if self.paused || self.override_time.is_some() {
+            return;
+        }
+        self.elapsed += delta * self.speed as f64;
+    }
+}
+
+/// Shifts a water body's time relative to the `WaterTime` clock, so bodies sharing the same
+/// settings don't move in lockstep.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct WaterTimeOffset(pub f32);
+
+/// The largest difference in height between any time and one `period` later, with the
+/// material's clock looping every `loop_period` seconds, or running freely if that's `None`.
+/// Sampled over a grid of points and start times, so anything much above `LOOP_TOLERANCE` means
+/// the surface visibly jumps where the loop restarts.
+///
+/// `None` if the method's heights only live on the GPU, so there's nothing to compare.
+pub fn loop_error<M: WaterMethod>(
+    material: &M,
+    loop_period: Option<f64>,
+    period: f64,
+) -> Option<f32> {
+    if !M::CPU_HEIGHT {
+        return None;
+    }
+    let mut start = material.clone();
+    let mut end = material.clone();
+    let mut max_error: f32 = 0.0;
+    for start_time in [0.0, period * 0.37, 1000.0 + period * 0.81] {
+        start.set_clock(WaveClock {
+            time: start_time,
+            loop_period,
+        });
+        end.set_clock(WaveClock {
+            time: start_time + period,
+            loop_period,
+        });
+        for x in 0..LOOP_CHECK_GRID {
+            for z in 0..LOOP_CHECK_GRID {
+                let position = (Vec2::new(x as f32, z as f32) / (LOOP_CHECK_GRID - 1) as f32 * 2.0
+                    - 1.0)
+                    * LOOP_CHECK_EXTENT;
+                max_error =
+                    max_error.max((start.height_at(position) - end.height_at(position)).abs());
+            }
+        }
+    }
+    Some(max_error)
+}
+
+/// Whether `material` is known to repeat after `period`, as `loop_error` measures it.
+pub fn loops_seamlessly<M: WaterMethod>(
+    material: &M,
+    loop_period: Option<f64>,
+    period: f64,
+) -> bool {
+    loop_error(material, loop_period, period).is_some_and(|error| error <= LOOP_TOLERANCE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopCheckResult {
+    /// The `loop_error` of the selected body's current material.
+    Error(f32),
+    /// Its method only has heights on the GPU, so the loop couldn't be checked.
+    NoCpuHeight,
+}
+
+/// A request from the time window to check that the selected water body loops, and its answer.
+#[derive(Resource, Debug, Default)]
+pub struct LoopCheck {
+    pub requested: bool,
+    pub result: Option<LoopCheckResult>,
+}
+
+pub struct WaterTimePlugin;
+
+impl Plugin for WaterTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterTime>()
+            .init_resource::<LoopCheck>()
+            .register_type::<WaterTime>()
+            .register_type::<WaterTimeOffset>()
+            .add_systems(
+                Update,
+                (time_ui, advance_water_time)
+                    .chain()
+                    .before(WaterMethodSet::Update),
+            );
+    }
+}
+
+fn advance_water_time(mut water_time: ResMut<WaterTime>, time: Res<Time>) {
+    water_time.advance(time.delta_seconds_f64());
+}
+
+fn time_ui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut water_time: ResMut<WaterTime>,
+    selected: Res<SelectedWater>,
+    offsets: Query<Option<&WaterTimeOffset>, With<WaterBody>>,
+    mut loop_check: ResMut<LoopCheck>,
+) {
+    let mut edited = water_time.clone();
+    let selected_offset = selected.0.and_then(|entity| {
+        Some((
+            entity,
+            offsets.get(entity).ok()?.copied().unwrap_or_default(),
+        ))
+    });
+    let mut offset = selected_offset.map(|(_, offset)| offset);
+
+    egui::Window::new("Time")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("time")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Playback");
+                    ui.horizontal(|ui| {
+                        let label = if edited.paused { "Play" } else { "Pause" };
+                        if ui.button(label).clicked() {
+                            edited.paused = !edited.paused;
+                        }
+                        if ui.button("Restart").clicked() {
+                            edited.set_elapsed(0.0);
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Speed");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::Slider::new(&mut edited.speed, -4.0..=4.0)
+                                .step_by(0.05)
+                                .suffix("x"),
+                        );
+                        if ui.button("Reset").clicked() {
+                            edited.speed = 1.0;
+                        }
+                    });
+                    ui.end_row();
+
+                    // Scrubbing the clock pauses it, so the frame stays put once it's let go
+                    ui.label("Time");
+                    let mut elapsed = edited.elapsed;
+                    let scrub = ui.add_enabled(
+                        edited.override_time.is_none(),
+                        egui::DragValue::new(&mut elapsed).speed(0.01).suffix(" s"),
+                    );
+                    if scrub.changed() {
+                        edited.set_elapsed(elapsed);
+                        edited.paused = true;
+                    }
+                    ui.end_row();
+
+                    ui.label("Override Time");
+                    ui.horizontal(|ui| {
+                        let mut overridden = edited.override_time.is_some();
+                        if ui.checkbox(&mut overridden, "").changed() {
+                            edited.override_time = overridden.then_some(edited.elapsed);
+                        }
+                        if let Some(override_time) = &mut edited.override_time {
+                            ui.add(egui::DragValue::new(override_time).speed(0.01).suffix(" s"));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Seamless Loop");
+                    ui.horizontal(|ui| {
+                        let mut looping = edited.loop_period.is_some();
+                        if ui.checkbox(&mut looping, "").changed() {
+                            edited.loop_period = looping.then_some(DEFAULT_LOOP_PERIOD);
+                            loop_check.result = None;
+                        }
+                        if let Some(loop_period) = &mut edited.loop_period {
+                            let period = ui.add(
+                                egui::DragValue::new(loop_period)
+                                    .speed(0.1)
+                                    .clamp_range(0.1..=3600.0)
+                                    .suffix(" s"),
+                            );
+                            if period.changed() {
+                                loop_check.result = None;
+                            }
+                            if ui.button("Verify").clicked() {
+                                loop_check.requested = true;
+                            }
+                            match loop_check.result {
+                                Some(LoopCheckResult::Error(error)) if error <= LOOP_TOLERANCE => {
+                                    ui.label("Seamless");
+                                }
+                                Some(LoopCheckResult::Error(error)) => {
+                                    ui.label(format!("Jumps by {error:.4} m"));
+                                }
+                                Some(LoopCheckResult::NoCpuHeight) => {
+                                    ui.label("Can't check this method's heights on the CPU");
+                                }
+                                None => {}
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    if let Some(offset) = &mut offset {
+                        ui.label("Body Time Offset");
+                        ui.add(egui::DragValue::new(&mut offset.0).speed(0.01).suffix(" s"));
+                        ui.end_row();
+                    }
+                });
+        });
+
+    water_time.set_if_neq(edited);
+    if let (Some((entity, previous)), Some(offset)) = (selected_offset, offset) {
+        if offset != previous {
+            commands.entity(entity).insert(offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_turborand::prelude::*;
+    use std::f32::consts::{PI, TAU};
+
+    use super::*;
+    use crate::{fbm_water::FbmWaterMaterial, fft_water::FftWaterMaterial, sum_water};
+
+    const PERIOD: f64 = 10.0;
+
+    fn sum_material() -> sum_water::SumWaterMaterial {
+        sum_water::SumWaterMaterial::random(
+            sum_water::WaveType::SteepSine,
+            &mut GlobalRng::with_seed(7),
+        )
+    }
+
+    fn phase_difference(a: f32, b: f32) -> f32 {
+        let difference = (a - b).rem_euclid(TAU);
+        difference.min(TAU - difference)
+    }
+
+    #[test]
+    fn sum_of_sines_loops_with_a_loop_period() {
+        assert!(loops_seamlessly(&sum_material(), Some(PERIOD), PERIOD));
+    }
+
+    #[test]
+    fn fbm_loops_with_a_loop_period() {
+        assert!(loops_seamlessly(
+            &FbmWaterMaterial::new(),
+            Some(PERIOD),
+            PERIOD
+        ));
+    }
+
+    #[test]
+    fn free_running_clock_does_not_loop() {
+        assert!(!loops_seamlessly(&sum_material(), None, PERIOD));
+        assert!(!loops_seamlessly(&FbmWaterMaterial::new(), None, PERIOD));
+    }
+
+    #[test]
+    fn gpu_only_methods_are_not_checked() {
+        assert_eq!(
+            loop_error(&FftWaterMaterial::default(), Some(PERIOD), PERIOD),
+            None
+        );
+        assert!(!loops_seamlessly(
+            &FftWaterMaterial::default(),
+            Some(PERIOD),
+            PERIOD
+        ));
+    }
+
+    #[test]
+    fn quantized_phase_repeats_after_the_period() {
+        for speed in [0.05, 0.7, 1.3, 4.2, -2.5] {
+            let clock = |time| WaveClock {
+                time,
+                loop_period: Some(PERIOD),
+            };
+            let start = clock(3.0).phase(speed);
+            let end = clock(3.0 + PERIOD).phase(speed);
+            assert!(phase_difference(start, end) < 1e-4, "speed {speed}");
+        }
+    }
+
+    #[test]
+    fn quantized_phase_rounds_to_whole_turns() {
+        // 0.7 rad/s is 1.11 turns per 10 s, so it runs at one turn per period
+        let clock = WaveClock {
+            time: PERIOD / 4.0,
+            loop_period: Some(PERIOD),
+        };
+        assert!(phase_difference(clock.phase(0.7), PI / 2.0) < 1e-4);
+    }
+
+    #[test]
+    fn slow_waves_keep_moving_when_quantized() {
+        let clock = WaveClock {
+            time: PERIOD / 2.0,
+            loop_period: Some(PERIOD),
+        };
+        assert!(phase_difference(clock.phase(0.01), PI) < 1e-4);
+        assert!(phase_difference(clock.phase(-0.01), PI) < 1e-4);
+        assert_eq!(clock.phase(0.0), 0.0);
+    }
+
+    #[test]
+    fn unquantized_phase_is_time_times_speed() {
+        let clock = WaveClock {
+            time: 1000.25,
+            loop_period: None,
+        };
+        let expected = (1000.25_f64 * 1.5).rem_euclid(std::f64::consts::TAU) as f32;
+        assert!(phase_difference(clock.phase(1.5), expected) < 1e-5);
+    }
+}
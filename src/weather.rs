@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Wind speed, in m/s, that the water methods' own settings are tuned for.
+pub const DEFAULT_WIND_SPEED: f32 = 10.0;
+pub const DEFAULT_GUSTINESS: f32 = 0.5;
+pub const DEFAULT_SEA_STATE: f32 = 3.0;
+
+/// Typical significant wave height in metres for each Douglas sea state, taken as the middle of
+/// its range.
+const SIGNIFICANT_WAVE_HEIGHTS: [f32; 10] =
+    [0.0, 0.05, 0.3, 0.875, 1.875, 3.25, 5.0, 7.5, 11.5, 14.0];
+
+/// Conditions shared by every water body. Each method that simulates its waves live maps them onto
+/// its own settings, so the default weather leaves those settings as they are and anything else
+/// makes every such body calmer or rougher at once.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct Weather {
+    /// In m/s.
+    pub wind_speed: f32,
+    /// The direction the wind blows towards, in degrees around the surface normal from its x axis.
+    pub wind_direction: f32,
+    /// How much the wind varies, from 0 (steady) to 1 (squally).
+    pub gustiness: f32,
+    /// Douglas sea state, from 0 (calm, glassy) to 9 (phenomenal).
+    pub sea_state: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather {
+            wind_speed: DEFAULT_WIND_SPEED,
+            wind_direction: 0.0,
+            gustiness: DEFAULT_GUSTINESS,
+            sea_state: DEFAULT_SEA_STATE,
+        }
+    }
+}
+
+impl Weather {
+    pub fn wind_angle(&self) -> f32 {
+        self.wind_direction.to_radians()
+    }
+
+    /// How much taller waves are than in the default weather, following the typical wave height of
+    /// the sea state.
+    pub fn height_scale(&self) -> f32 {
+        significant_wave_height(self.sea_state) / significant_wave_height(DEFAULT_SEA_STATE)
+    }
+
+    /// How much faster waves travel than in the default weather. A stronger wind builds longer
+    /// waves, and deep water waves travel with the square root of their wavelength.
+    pub fn speed_scale(&self) -> f32 {
+        (self.wind_speed.max(0.0) / DEFAULT_WIND_SPEED).sqrt()
+    }
+
+    /// How much more widely wave directions spread around the wind than in the default weather.
+    pub fn spread_scale(&self) -> f32 {
+        self.gustiness.max(0.0) / DEFAULT_GUSTINESS
+    }
+
+    /// How much taller short, wind-driven ripples are than in the default weather. Gusts keep
+    /// catching the surface, so they roughen it at small scales without building bigger waves.
+    pub fn gust_scale(&self) -> f32 {
+        self.gustiness.max(0.0) / DEFAULT_GUSTINESS
+    }
+}
+
+fn significant_wave_height(sea_state: f32) -> f32 {
+    let last = SIGNIFICANT_WAVE_HEIGHTS.len() - 1;
+    let sea_state = sea_state.clamp(0.0, last as f32);
+    let lower = (sea_state.floor() as usize).min(last - 1);
+    let t = sea_state - lower as f32;
+    SIGNIFICANT_WAVE_HEIGHTS[lower] * (1.0 - t) + SIGNIFICANT_WAVE_HEIGHTS[lower + 1] * t
+}